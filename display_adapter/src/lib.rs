@@ -0,0 +1,60 @@
+//! `#[display_adapter]` turns a method that writes to a `Formatter` into one that returns an
+//! `impl Display`, so that callers can print it with `{}` or call `to_string()` on it.
+//!
+//! The annotated function's last parameter must be the `&mut Formatter` to write to, and its body
+//! must evaluate to a `fmt::Result`. Every other reference parameter, including `&self`, is
+//! borrowed for as long as the returned value lives.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, FnArg, ItemFn, Lifetime, Type};
+
+#[proc_macro_attribute]
+pub fn display_adapter(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    let lifetime = Lifetime::new("'__display_adapter", Span::call_site());
+
+    let mut inputs: Vec<FnArg> = func.sig.inputs.iter().cloned().collect();
+    let formatter = match inputs.pop() {
+        Some(FnArg::Typed(formatter)) => formatter.pat,
+        _ => {
+            return syn::Error::new_spanned(&func.sig, "expected a `&mut Formatter` as the last parameter")
+                .to_compile_error()
+                .into();
+        },
+    };
+    for input in &mut inputs {
+        match input {
+            FnArg::Receiver(receiver) => if let Some((_, receiver_lifetime)) = &mut receiver.reference {
+                *receiver_lifetime = Some(lifetime.clone());
+            },
+            FnArg::Typed(input) => if let Type::Reference(reference) = &mut *input.ty {
+                if reference.lifetime.is_none() {
+                    reference.lifetime = Some(lifetime.clone());
+                }
+            },
+        }
+    }
+
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+    let name = &func.sig.ident;
+    let mut generics = func.sig.generics.clone();
+    generics.params.insert(0, parse_quote!(#lifetime));
+    let where_clause = &generics.where_clause;
+    let body = &func.block;
+    let expanded = quote! {
+        #(#attrs)*
+        #vis fn #name #generics (#(#inputs),*) -> impl std::fmt::Display + #lifetime #where_clause {
+            struct Adapter<F: Fn(&mut std::fmt::Formatter) -> std::fmt::Result>(F);
+            impl<F: Fn(&mut std::fmt::Formatter) -> std::fmt::Result> std::fmt::Display for Adapter<F> {
+                fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    (self.0)(f)
+                }
+            }
+            Adapter(move |#formatter: &mut std::fmt::Formatter| -> std::fmt::Result #body)
+        }
+    };
+    expanded.into()
+}
@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use crate::ty::{IntWidth, FloatWidth};
+
 #[derive(Copy, Clone, Debug)]
 pub enum Arch {
     X86_64,
@@ -7,12 +9,119 @@ pub enum Arch {
     SharpLR35902,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Alignments of a type in bytes: `abi` is the minimum the target requires, and `preferred` is
+/// what it performs best with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Alignment {
+    pub abi: usize,
+    pub preferred: usize,
+}
+
+impl Alignment {
+    const fn new(abi: usize, preferred: usize) -> Self {
+        Alignment { abi, preferred }
+    }
+}
+
+/// How a target lays out data in memory
+#[derive(Clone, Debug)]
+pub struct TargetDataLayout {
+    pub endianness: Endianness,
+    /// Size of pointers in bits
+    pub pointer_size: usize,
+    pub pointer_align: Alignment,
+    /// Whether address 0 can hold data or code. If it can't, a null pointer is an invalid value
+    /// that enums can use to encode other variants.
+    pub null_address_is_valid: bool,
+    pub i8_align: Alignment,
+    pub i16_align: Alignment,
+    pub i32_align: Alignment,
+    pub i64_align: Alignment,
+    pub f32_align: Alignment,
+    pub f64_align: Alignment,
+    /// Alignment of the stack pointer at function calls, in bytes
+    pub stack_align: usize,
+    /// Whether the target has floating point instructions, as opposed to needing them emulated
+    pub has_native_floats: bool,
+    /// Width of the general purpose registers in bits
+    pub register_width: usize,
+}
+
+impl TargetDataLayout {
+    pub fn int_align(&self, width: IntWidth) -> Alignment {
+        match width {
+            IntWidth::W8 => self.i8_align,
+            IntWidth::W16 => self.i16_align,
+            IntWidth::W32 => self.i32_align,
+            IntWidth::W64 => self.i64_align,
+            IntWidth::Pointer => self.pointer_align,
+        }
+    }
+
+    pub fn float_align(&self, width: FloatWidth) -> Alignment {
+        match width {
+            FloatWidth::W32 => self.f32_align,
+            FloatWidth::W64 => self.f64_align,
+        }
+    }
+}
+
+const X86_64_DATA_LAYOUT: TargetDataLayout = TargetDataLayout {
+    endianness: Endianness::Little,
+    pointer_size: 64,
+    pointer_align: Alignment::new(8, 8),
+    null_address_is_valid: false,
+    i8_align: Alignment::new(1, 1),
+    i16_align: Alignment::new(2, 2),
+    i32_align: Alignment::new(4, 4),
+    i64_align: Alignment::new(8, 8),
+    f32_align: Alignment::new(4, 4),
+    f64_align: Alignment::new(8, 8),
+    stack_align: 16,
+    has_native_floats: true,
+    register_width: 64,
+};
+
+// The Gameboy's CPU accesses memory a byte at a time, so nothing needs to be aligned
+const SHARP_LR35902_DATA_LAYOUT: TargetDataLayout = TargetDataLayout {
+    endianness: Endianness::Little,
+    pointer_size: 16,
+    pointer_align: Alignment::new(1, 1),
+    // Address 0 is in ROM, and is where `RST 0` jumps to
+    null_address_is_valid: true,
+    i8_align: Alignment::new(1, 1),
+    i16_align: Alignment::new(1, 1),
+    i32_align: Alignment::new(1, 1),
+    i64_align: Alignment::new(1, 1),
+    f32_align: Alignment::new(1, 1),
+    f64_align: Alignment::new(1, 1),
+    stack_align: 1,
+    has_native_floats: false,
+    register_width: 8,
+};
+
 impl Arch {
-    /// Size of the target architecture's pointers in bits
-    pub fn pointer_size(self) -> usize {
+    pub fn data_layout(self) -> &'static TargetDataLayout {
         match self {
-            Arch::X86_64 => 64,
-            Arch::SharpLR35902 => 16,
+            Arch::X86_64 => &X86_64_DATA_LAYOUT,
+            Arch::SharpLR35902 => &SHARP_LR35902_DATA_LAYOUT,
         }
     }
+
+    /// Size of the target architecture's pointers in bits
+    pub fn pointer_size(self) -> usize {
+        self.data_layout().pointer_size
+    }
+
+    /// Whether address 0 can hold data or code. If it can't, a null pointer is an invalid value
+    /// that enums can use to encode other variants.
+    pub fn null_address_is_valid(self) -> bool {
+        self.data_layout().null_address_is_valid
+    }
 }
@@ -267,8 +267,34 @@ pub enum PatternKind {
     AnonymousCatchAll(SourceRange),
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum Intrinsic {
+/// Declares `Intrinsic` along with the names that textual MIR uses for its variants, so that the
+/// printer and parser can't disagree
+macro_rules! intrinsics {
+    ($($intr:ident,)*) => {
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum Intrinsic {
+            $($intr,)*
+        }
+
+        impl Intrinsic {
+            /// The name of the intrinsic in textual MIR
+            pub fn mir_name(self) -> &'static str {
+                match self {
+                    $(Intrinsic::$intr => stringify!($intr),)*
+                }
+            }
+
+            pub fn from_mir_name(name: &str) -> Option<Intrinsic> {
+                match name {
+                    $(stringify!($intr) => Some(Intrinsic::$intr),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+intrinsics! {
     Mult,
     Div,
     Mod,
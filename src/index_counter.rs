@@ -12,6 +12,7 @@ impl<I: Idx + AddAssign<usize>> IndexCounter<I> {
         IndexCounter { next_id: I::from_usize(0) }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> I {
         let next = self.next_id;
         self.next_id += 1;
@@ -23,6 +24,8 @@ impl<I: Idx + AddAssign<usize>> IndexCounter<I> {
     }
 
     pub fn len(&self) -> usize { self.next_id.index() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
 }
 
 impl<I: Idx + AddAssign<usize>> Default for IndexCounter<I> {
@@ -11,6 +11,7 @@ use display_adapter::display_adapter;
 use hir::{HirCode, Item};
 use mir::{MirCode, Instr, VOID_INSTR};
 use source_info::SourceRange;
+use ty::TypeInterner;
 
 define_index_type!(pub struct OpId = u32;);
 define_index_type!(pub struct BlockId = u32;);
@@ -43,7 +44,10 @@ pub struct Block {
 }
 pub struct Code {
     pub blocks: IndexVec<BlockId, Block>,
+    /// Add and replace MIR instrs with `push_mir_instr` and `set_mir_instr`, which keep the use
+    /// lists up to date. After writing to this directly, call `rebuild_uses`.
     pub ops: IndexVec<OpId, Op>,
+    pub types: TypeInterner,
     pub hir_code: HirCode,
     pub mir_code: MirCode,
 }
@@ -53,6 +57,7 @@ impl Default for Code {
         let mut val = Code {
             blocks: IndexVec::default(),
             ops: index_vec![Op::MirInstr(Instr::Void)],
+            types: TypeInterner::default(),
             hir_code: HirCode::default(),
             mir_code: MirCode::default(),
         };
@@ -84,7 +89,17 @@ impl Code {
                     }
                 },
                 Op::MirInstr(ref instr) => {
-                    writeln!(w, " = mir.{:?}", instr)?;
+                    if let Some(name) = self.mir_code.instr_names.get(&id) {
+                        write!(w, " ")?;
+                        mir::text::write_str_lit(w, name.as_bytes())?;
+                    }
+                    write!(w, " = mir.")?;
+                    self.write_instr(w, instr)?;
+                    match self.mir_code.source_ranges.get(&id) {
+                        Some(range) if range.start != usize::MAX => write!(w, " @{}..{}", range.start, range.end)?,
+                        _ => {},
+                    }
+                    writeln!(w)?;
                 },
             }
         }
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
+use std::hash::{Hash, Hasher};
+use std::mem;
 
 use index_vec::{IndexVec, define_index_type};
-use smallvec::SmallVec;
+use smallvec::{SmallVec, smallvec};
 use string_interner::DefaultSymbol as Sym;
 use display_adapter::display_adapter;
 
@@ -11,25 +13,57 @@ use crate::ty::Type;
 use crate::{Code, BlockId, OpId};
 use crate::source_info::SourceRange;
 
+pub mod verify;
+pub mod text;
+pub mod parse;
+pub mod eval;
+pub mod interp;
+pub mod builder;
+pub mod cfg;
+pub mod dom;
+pub mod uses;
+pub mod rewrite;
+pub mod mem2reg;
+pub mod fold;
+pub mod dce;
+pub mod simplify_cfg;
+pub mod inline;
+pub mod gvn;
+pub mod loops;
+pub mod licm;
+pub mod sccp;
+pub mod liveness;
+pub mod layout;
+pub mod pass;
+
 define_index_type!(pub struct FuncId = u32;);
 define_index_type!(pub struct StaticId = u32;);
 define_index_type!(pub struct StrId = u32;);
 
 pub const VOID_INSTR: OpId = OpId::from_usize_unchecked(0);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SwitchCase {
     pub value: Const,
     pub bb: BlockId,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// An incoming value of a `Phi`: `value` is the phi's value when control arrives from `bb`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PhiEdge {
+    pub bb: BlockId,
+    pub value: OpId,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Instr {
     Void,
     Const(Const),
     Alloca(Type),
     LogicalNot(OpId),
     Call { arguments: SmallVec<[OpId; 2]>, generic_arguments: Vec<Type>, func: FuncId },
+    /// Calls the function that `callee`, a value of function pointer type, points to
+    CallIndirect { arguments: SmallVec<[OpId; 2]>, callee: OpId },
     Intrinsic { arguments: SmallVec<[OpId; 2]>, ty: Type, intr: Intrinsic },
     Reinterpret(OpId, Type),
     Truncate(OpId, Type),
@@ -49,6 +83,13 @@ pub enum Instr {
     IndirectFieldAccess { val: OpId, index: usize },
     Variant { enuum: EnumId, index: usize, payload: OpId },
     DiscriminantAccess { val: OpId },
+    /// The element at `index` of the array value `val`
+    ElementAccess { val: OpId, index: OpId },
+    /// A pointer to the element at `index` of the array that `ptr` points to, or of the slice `ptr`
+    ElementAddress { ptr: OpId, index: OpId },
+    /// A slice of the `len` elements starting at the element that `ptr` points to
+    MakeSlice { ptr: OpId, len: OpId },
+    SliceLen(OpId),
     Ret(OpId),
     Br(BlockId),
     CondBr { condition: OpId, true_bb: BlockId, false_bb: BlockId },
@@ -56,31 +97,230 @@ pub enum Instr {
     GenericParam(GenericParamId),
     /// Only valid at the beginning of a function, right after the void instruction
     Parameter(Type),
+    /// Only valid at the beginning of a block, which must have exactly one incoming edge per
+    /// predecessor
+    Phi { incoming: SmallVec<[PhiEdge; 2]> },
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Floats are compared and hashed by their bits, so that `Const` can be `Eq` and `Hash`: a NaN is
+/// equal to a NaN with the same bits, and 0.0 is not equal to -0.0
+#[derive(Clone, Debug)]
 pub enum Const {
     Int { lit: u64, ty: Type },
     Float { lit: f64, ty: Type },
     Str { id: StrId, ty: Type },
+    /// The address of the function `id`, of the function pointer type `ty`
+    Func { id: FuncId, ty: Type },
     Bool(bool),
     Ty(Type),
     Mod(ModScopeId),
     BasicVariant { enuum: EnumId, index: usize },
     StructLit { fields: Vec<Const>, id: StructId },
+    /// `ty` is the type of the whole array
+    Array { elems: Vec<Const>, ty: Type },
+}
+
+impl PartialEq for Const {
+    fn eq(&self, other: &Const) -> bool {
+        match (self, other) {
+            (Const::Int { lit: a, ty: a_ty }, Const::Int { lit: b, ty: b_ty }) => a == b && a_ty == b_ty,
+            (Const::Float { lit: a, ty: a_ty }, Const::Float { lit: b, ty: b_ty }) => a.to_bits() == b.to_bits() && a_ty == b_ty,
+            (Const::Str { id: a, ty: a_ty }, Const::Str { id: b, ty: b_ty }) => a == b && a_ty == b_ty,
+            (Const::Func { id: a, ty: a_ty }, Const::Func { id: b, ty: b_ty }) => a == b && a_ty == b_ty,
+            (Const::Bool(a), Const::Bool(b)) => a == b,
+            (Const::Ty(a), Const::Ty(b)) => a == b,
+            (Const::Mod(a), Const::Mod(b)) => a == b,
+            (Const::BasicVariant { enuum: a, index: a_index }, Const::BasicVariant { enuum: b, index: b_index }) => a == b && a_index == b_index,
+            (Const::StructLit { fields: a, id: a_id }, Const::StructLit { fields: b, id: b_id }) => a == b && a_id == b_id,
+            (Const::Array { elems: a, ty: a_ty }, Const::Array { elems: b, ty: b_ty }) => a == b && a_ty == b_ty,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Const {}
+
+impl Hash for Const {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Const::Int { lit, ty } => {
+                lit.hash(state);
+                ty.hash(state);
+            },
+            Const::Float { lit, ty } => {
+                lit.to_bits().hash(state);
+                ty.hash(state);
+            },
+            Const::Str { id, ty } => {
+                id.hash(state);
+                ty.hash(state);
+            },
+            Const::Func { id, ty } => {
+                id.hash(state);
+                ty.hash(state);
+            },
+            Const::Bool(val) => val.hash(state),
+            Const::Ty(ty) => ty.hash(state),
+            Const::Mod(id) => id.hash(state),
+            Const::BasicVariant { enuum, index } => {
+                enuum.hash(state);
+                index.hash(state);
+            },
+            Const::StructLit { fields, id } => {
+                fields.hash(state);
+                id.hash(state);
+            },
+            Const::Array { elems, ty } => {
+                elems.hash(state);
+                ty.hash(state);
+            },
+        }
+    }
+}
+
+impl Instr {
+    pub fn is_terminator(&self) -> bool {
+        matches!(self, Instr::Ret(_) | Instr::Br(_) | Instr::CondBr { .. } | Instr::SwitchBr { .. })
+    }
+
+    /// Whether executing this instr can have an effect other than producing its value. Such
+    /// instrs can't be removed even if their value is unused. Terminators and `Parameter`s
+    /// count, since removing them changes control flow or the function's signature.
+    pub fn has_side_effects(&self) -> bool {
+        match self {
+            Instr::Store { .. } | Instr::Call { .. } | Instr::CallIndirect { .. } | Instr::Parameter(_) => true,
+            Instr::Intrinsic { intr, .. } => matches!(
+                intr,
+                Intrinsic::Print | Intrinsic::Panic | Intrinsic::Malloc | Intrinsic::Free | Intrinsic::PrintType
+            ),
+            _ => self.is_terminator(),
+        }
+    }
+
+    /// Whether this instr always produces the same value from the same operands, without reading
+    /// or writing memory or having any other effect. Two pure instrs that compare equal compute
+    /// the same value, although some, like `Div`, may still trap.
+    pub fn is_pure(&self) -> bool {
+        match self {
+            Instr::Const(_) | Instr::LogicalNot(_) | Instr::Reinterpret(..) | Instr::Truncate(..)
+                | Instr::SignExtend(..) | Instr::ZeroExtend(..) | Instr::FloatCast(..)
+                | Instr::FloatToInt(..) | Instr::IntToFloat(..) | Instr::AddressOfStatic(_)
+                | Instr::Pointer { .. } | Instr::Struct { .. } | Instr::Enum { .. }
+                | Instr::StructLit { .. } | Instr::DirectFieldAccess { .. }
+                | Instr::IndirectFieldAccess { .. } | Instr::Variant { .. }
+                | Instr::DiscriminantAccess { .. } | Instr::ElementAccess { .. }
+                | Instr::ElementAddress { .. } | Instr::MakeSlice { .. } | Instr::SliceLen(_)
+                | Instr::GenericParam(_) => true,
+            Instr::Intrinsic { .. } => !self.has_side_effects(),
+            Instr::Void | Instr::Alloca(_) | Instr::Call { .. } | Instr::CallIndirect { .. }
+                | Instr::Load(_) | Instr::Store { .. }
+                | Instr::Ret(_) | Instr::Br(_) | Instr::CondBr { .. } | Instr::SwitchBr { .. }
+                | Instr::Parameter(_) | Instr::Phi { .. } => false,
+        }
+    }
+
+    /// Whether this instr can trap at runtime for some operands, as a division by zero or an
+    /// out-of-bounds index does
+    pub fn can_trap(&self) -> bool {
+        matches!(
+            self,
+            Instr::Intrinsic { intr: Intrinsic::Div, .. } | Instr::Intrinsic { intr: Intrinsic::Mod, .. }
+                | Instr::FloatToInt(..) | Instr::ElementAccess { .. } | Instr::ElementAddress { .. }
+        )
+    }
+
+    pub fn is_phi(&self) -> bool {
+        matches!(self, Instr::Phi { .. })
+    }
+
+    /// Returns the ops read by this instr, in order. An op appears once for each time it is read.
+    pub fn operands(&self) -> SmallVec<[OpId; 2]> {
+        match self {
+            Instr::Void | Instr::Const(_) | Instr::Alloca(_) | Instr::AddressOfStatic(_)
+                | Instr::Br(_) | Instr::GenericParam(_) | Instr::Parameter(_) => SmallVec::new(),
+            &Instr::LogicalNot(op) | &Instr::Reinterpret(op, _) | &Instr::Truncate(op, _)
+                | &Instr::SignExtend(op, _) | &Instr::ZeroExtend(op, _) | &Instr::FloatCast(op, _)
+                | &Instr::FloatToInt(op, _) | &Instr::IntToFloat(op, _) | &Instr::Load(op)
+                | &Instr::Pointer { op, .. } | &Instr::DirectFieldAccess { val: op, .. }
+                | &Instr::IndirectFieldAccess { val: op, .. } | &Instr::Variant { payload: op, .. }
+                | &Instr::DiscriminantAccess { val: op } | &Instr::SliceLen(op) | &Instr::Ret(op)
+                | &Instr::CondBr { condition: op, .. } | &Instr::SwitchBr { scrutinee: op, .. } => smallvec![op],
+            &Instr::Store { location, value } => smallvec![location, value],
+            &Instr::ElementAccess { val: op, index } | &Instr::ElementAddress { ptr: op, index } => smallvec![op, index],
+            &Instr::MakeSlice { ptr, len } => smallvec![ptr, len],
+            Instr::Call { arguments, .. } | Instr::Intrinsic { arguments, .. } => arguments.clone(),
+            Instr::CallIndirect { arguments, callee } => std::iter::once(*callee).chain(arguments.iter().copied()).collect(),
+            Instr::Struct { fields, .. } | Instr::StructLit { fields, .. } => fields.clone(),
+            Instr::Enum { variants, .. } => variants.clone(),
+            Instr::Phi { incoming } => incoming.iter().map(|edge| edge.value).collect(),
+        }
+    }
+
+    /// Returns mutable references to the ops read by this instr, in the same order as `operands`
+    pub fn operands_mut(&mut self) -> SmallVec<[&mut OpId; 2]> {
+        match self {
+            Instr::Void | Instr::Const(_) | Instr::Alloca(_) | Instr::AddressOfStatic(_)
+                | Instr::Br(_) | Instr::GenericParam(_) | Instr::Parameter(_) => SmallVec::new(),
+            Instr::LogicalNot(op) | Instr::Reinterpret(op, _) | Instr::Truncate(op, _)
+                | Instr::SignExtend(op, _) | Instr::ZeroExtend(op, _) | Instr::FloatCast(op, _)
+                | Instr::FloatToInt(op, _) | Instr::IntToFloat(op, _) | Instr::Load(op)
+                | Instr::Pointer { op, .. } | Instr::DirectFieldAccess { val: op, .. }
+                | Instr::IndirectFieldAccess { val: op, .. } | Instr::Variant { payload: op, .. }
+                | Instr::DiscriminantAccess { val: op } | Instr::SliceLen(op) | Instr::Ret(op)
+                | Instr::CondBr { condition: op, .. } | Instr::SwitchBr { scrutinee: op, .. } => smallvec![op],
+            Instr::Store { location, value } => smallvec![location, value],
+            Instr::ElementAccess { val: op, index } | Instr::ElementAddress { ptr: op, index } => smallvec![op, index],
+            Instr::MakeSlice { ptr, len } => smallvec![ptr, len],
+            Instr::Call { arguments, .. } | Instr::Intrinsic { arguments, .. } => arguments.iter_mut().collect(),
+            Instr::CallIndirect { arguments, callee } => std::iter::once(callee).chain(arguments.iter_mut()).collect(),
+            Instr::Struct { fields, .. } | Instr::StructLit { fields, .. } => fields.iter_mut().collect(),
+            Instr::Enum { variants, .. } => variants.iter_mut().collect(),
+            Instr::Phi { incoming } => incoming.iter_mut().map(|edge| &mut edge.value).collect(),
+        }
+    }
+
+    /// Returns the blocks that this instr may branch to. A block appears once for each edge to it.
+    pub fn successors(&self) -> SmallVec<[BlockId; 2]> {
+        match self {
+            &Instr::Br(bb) => smallvec![bb],
+            &Instr::CondBr { true_bb, false_bb, .. } => smallvec![true_bb, false_bb],
+            Instr::SwitchBr { cases, catch_all_bb, .. } => cases.iter()
+                .map(|case| case.bb)
+                .chain(std::iter::once(*catch_all_bb))
+                .collect(),
+            _ => SmallVec::new(),
+        }
+    }
+
+    /// Returns mutable references to the blocks that this instr may branch to, in the same order
+    /// as `successors`
+    pub fn successors_mut(&mut self) -> SmallVec<[&mut BlockId; 2]> {
+        match self {
+            Instr::Br(bb) => smallvec![bb],
+            Instr::CondBr { true_bb, false_bb, .. } => smallvec![true_bb, false_bb],
+            Instr::SwitchBr { cases, catch_all_bb, .. } => cases.iter_mut()
+                .map(|case| &mut case.bb)
+                .chain(std::iter::once(catch_all_bb))
+                .collect(),
+            _ => SmallVec::new(),
+        }
+    }
 }
 
 impl Const {
     pub fn ty(&self) -> Type {
         match self {
-            Const::Int { ty, .. } => ty.clone(),
-            Const::Float { ty, .. } => ty.clone(),
-            Const::Str { ty, .. } => ty.clone(),
+            Const::Int { ty, .. } => *ty,
+            Const::Float { ty, .. } => *ty,
+            Const::Str { ty, .. } => *ty,
+            Const::Func { ty, .. } => *ty,
             Const::Bool(_) => Type::Bool,
             Const::Ty(_) => Type::Ty,
             &Const::BasicVariant { enuum, .. } => Type::Enum(enuum),
             Const::Mod(_) => Type::Mod,
             &Const::StructLit { id, .. } => Type::Struct(id),
+            Const::Array { ty, .. } => *ty,
         }
     }
 }
@@ -88,17 +328,31 @@ impl Const {
 #[derive(Default, Debug)]
 pub struct InstrNamespace {
     name_usages: HashMap<String, u16>,
+    /// Every name handed out by `insert` or recorded by `reserve`
+    used_names: HashSet<String>,
 }
 
 impl InstrNamespace {
+    /// Returns `name`, or `name` with the first numeric suffix that makes it unique
     pub fn insert(&mut self, name: impl Into<String>) -> String {
-        let mut name = name.into();
+        let name = name.into();
         let entry = self.name_usages.entry(name.clone()).or_default();
-        if *entry > 0 {
-            name = format!("{}.{}", name, *entry);
+        loop {
+            let candidate = if *entry > 0 {
+                format!("{}.{}", name, *entry)
+            } else {
+                name.clone()
+            };
+            *entry += 1;
+            if self.used_names.insert(candidate.clone()) {
+                return candidate;
+            }
         }
-        *entry += 1;
-        name
+    }
+
+    /// Records `name` exactly as given, so that `insert` never hands it out
+    pub fn reserve(&mut self, name: impl Into<String>) {
+        self.used_names.insert(name.into());
     }
 }
 
@@ -124,14 +378,21 @@ impl Code {
             .count()
     }
 
+    /// The type of a pointer to `func`, made from the types of its parameters and its return type
+    pub fn func_ptr_ty(&self, func: &Function) -> Type {
+        let params: Vec<Type> = func.blocks.first().into_iter()
+            .flat_map(|&entry| &self.blocks[entry].ops)
+            .filter_map(|&op| match self.ops[op].as_mir_instr() {
+                Some(&Instr::Parameter(ty)) => Some(ty),
+                _ => None,
+            })
+            .collect();
+        self.types.function(&params, func.ret_ty)
+    }
+
     #[display_adapter]
     pub fn display_func(&self, func: &Function, name: &str, w: &mut Formatter) {
-        writeln!(w, "fn {}() {{", name)?;
-        for &block in &func.blocks {
-            write!(w, "%bb{}:\n{}", block.index(), self.display_block(block))?;
-        }
-        writeln!(w, "}}")?;
-        Ok(())
+        self.write_func(w, None, func, Some(name))
     }
 }
 
@@ -149,12 +410,33 @@ pub struct StructLayout {
     pub stride: usize,
 }
 
+#[derive(Clone)]
+pub struct Enum {
+    /// The payload type of each variant, `Void` for variants without one
+    pub variant_tys: SmallVec<[Type; 2]>,
+    pub layout: EnumLayout,
+}
+
 #[derive(Clone)]
 pub struct EnumLayout {
     pub payload_offsets: SmallVec<[usize; 2]>,
     pub alignment: usize,
     pub size: usize,
     pub stride: usize,
+    pub discriminant: DiscriminantEncoding,
+}
+
+/// Where an enum value records which variant it is
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiscriminantEncoding {
+    /// The enum has at most one variant, so nothing is stored
+    None,
+    /// The variant index is stored as an unsigned int of type `ty` at `offset`
+    Tag { offset: usize, ty: Type },
+    /// The variant `dataful_variant` is stored as just its payload, and the other variants as an
+    /// otherwise invalid value of the unsigned int of type `ty` at `offset` in that payload. The
+    /// `n`th of the other variants, in order, is stored as `niche_start + n`.
+    Niche { offset: usize, ty: Type, dataful_variant: usize, niche_start: u64 },
 }
 
 #[derive(Debug)]
@@ -174,9 +456,11 @@ pub struct MirCode {
     pub functions: IndexVec<FuncId, Function>,
     pub statics: IndexVec<StaticId, Static>,
     pub structs: HashMap<StructId, Struct>,
-    pub enums: HashMap<EnumId, EnumLayout>,
+    pub enums: HashMap<EnumId, Enum>,
     pub source_ranges: HashMap<OpId, SourceRange>,
     pub instr_names: HashMap<OpId, String>,
+    /// For each op, the MIR instrs that read it. Maintained by `Code::push_mir_instr` and friends.
+    uses: HashMap<OpId, Vec<OpId>>,
     block_states: HashMap<BlockId, BlockState>,
 }
 
@@ -201,6 +485,7 @@ impl MirCode {
             enums: HashMap::new(),
             source_ranges: HashMap::new(),
             instr_names: HashMap::new(),
+            uses: HashMap::new(),
             block_states: HashMap::new(),
         }
     }
@@ -233,6 +518,15 @@ impl MirCode {
         }
     }
 
+    /// Marks an ended block as started again, after its terminator has been removed
+    pub(crate) fn reopen_block(&mut self, block: BlockId) {
+        *self.get_block_state(block) = BlockState::Started;
+    }
+
+    pub(crate) fn is_block_ended(&self, block: BlockId) -> bool {
+        matches!(self.block_states.get(&block), Some(BlockState::Ended))
+    }
+
     pub fn first_unended_block(&self, func: &Function) -> Option<BlockId> {
         func.blocks.iter().find(|&block| {
             let state = &self.block_states[block];
@@ -0,0 +1,282 @@
+use smallvec::SmallVec;
+use string_interner::DefaultSymbol as Sym;
+
+use crate::hir::{Intrinsic, StructId, EnumId, GenericParamId};
+use crate::mir::{Const, Function, Instr, SwitchCase, PhiEdge, FuncId, StaticId, StartBlockError};
+use crate::source_info::SourceRange;
+use crate::ty::Type;
+use crate::{Code, Block, BlockId, OpId};
+
+#[derive(Debug)]
+pub enum BuildError {
+    /// No insertion block has been selected with `MirBuilder::switch_to_block`
+    NoInsertionBlock,
+    /// The insertion block already ends in a terminator
+    BlockEnded(BlockId),
+    /// `Parameter` instrs may only be inserted at the start of the entry block
+    MisplacedParameter,
+    /// `Phi` instrs may only be inserted before any other instr in a block
+    MisplacedPhi,
+    /// The op is not a `Phi` instr
+    NotPhi(OpId),
+    /// A block in the function was never terminated
+    BlockNotEnded(BlockId),
+}
+
+/// Builds MIR for a single function, keeping track of the insertion block, instr names and
+/// source ranges
+pub struct MirBuilder<'a> {
+    code: &'a mut Code,
+    func: FuncId,
+    block: Option<BlockId>,
+}
+
+impl<'a> MirBuilder<'a> {
+    /// Creates a builder that appends to the existing function `func`. No insertion block is selected.
+    pub fn new(code: &'a mut Code, func: FuncId) -> Self {
+        MirBuilder { code, func, block: None }
+    }
+
+    /// Creates a new function with an entry block, and selects the entry block for insertion
+    pub fn create_function(code: &'a mut Code, name: Option<Sym>, ret_ty: Type) -> Self {
+        let func = code.mir_code.functions.push(
+            Function {
+                name,
+                ret_ty,
+                ..Default::default()
+            }
+        );
+        let mut builder = MirBuilder::new(code, func);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry).unwrap();
+        builder
+    }
+
+    pub fn code(&self) -> &Code { self.code }
+
+    pub fn func(&self) -> FuncId { self.func }
+
+    pub fn function(&self) -> &Function { &self.code.mir_code.functions[self.func] }
+
+    pub fn insertion_block(&self) -> Option<BlockId> { self.block }
+
+    /// Creates a new block at the end of the function, without selecting it
+    pub fn create_block(&mut self) -> BlockId {
+        let block = self.code.blocks.push(Block::default());
+        self.code.mir_code.functions[self.func].blocks.push(block);
+        block
+    }
+
+    pub fn switch_to_block(&mut self, block: BlockId) -> Result<(), BuildError> {
+        match self.code.mir_code.start_block(block) {
+            Ok(()) => {
+                self.block = Some(block);
+                Ok(())
+            },
+            Err(StartBlockError::BlockEnded) => Err(BuildError::BlockEnded(block)),
+        }
+    }
+
+    /// Names `op` through the function's instr namespace, returning the (possibly disambiguated) name
+    pub fn name(&mut self, op: OpId, name: impl Into<String>) -> String {
+        let name = self.code.mir_code.functions[self.func].instr_namespace.insert(name);
+        self.code.mir_code.instr_names.insert(op, name.clone());
+        name
+    }
+
+    /// Checks that every block in the function has been terminated
+    pub fn finish(self) -> Result<FuncId, BuildError> {
+        let func = &self.code.mir_code.functions[self.func];
+        match self.code.mir_code.first_unended_block(func) {
+            Some(block) => Err(BuildError::BlockNotEnded(block)),
+            None => Ok(self.func),
+        }
+    }
+
+    fn push(&mut self, instr: Instr, range: SourceRange) -> Result<OpId, BuildError> {
+        let block = self.block.ok_or(BuildError::NoInsertionBlock)?;
+        if let Err(StartBlockError::BlockEnded) = self.code.mir_code.start_block(block) {
+            return Err(BuildError::BlockEnded(block));
+        }
+        let is_terminator = instr.is_terminator();
+        let op = self.code.push_mir_instr(instr);
+        self.code.blocks[block].ops.push(op);
+        self.code.mir_code.source_ranges.insert(op, range);
+        if is_terminator {
+            self.code.mir_code.end_block(block).unwrap();
+        }
+        Ok(op)
+    }
+
+    pub fn void(&mut self, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Void, range)
+    }
+
+    pub fn konst(&mut self, konst: Const, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Const(konst), range)
+    }
+
+    pub fn alloca(&mut self, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Alloca(ty), range)
+    }
+
+    pub fn logical_not(&mut self, op: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::LogicalNot(op), range)
+    }
+
+    pub fn call(&mut self, func: FuncId, arguments: SmallVec<[OpId; 2]>, generic_arguments: Vec<Type>, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Call { arguments, generic_arguments, func }, range)
+    }
+
+    pub fn call_indirect(&mut self, callee: OpId, arguments: SmallVec<[OpId; 2]>, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::CallIndirect { arguments, callee }, range)
+    }
+
+    pub fn intrinsic(&mut self, intr: Intrinsic, arguments: SmallVec<[OpId; 2]>, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Intrinsic { arguments, ty, intr }, range)
+    }
+
+    pub fn reinterpret(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Reinterpret(op, ty), range)
+    }
+
+    pub fn truncate(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Truncate(op, ty), range)
+    }
+
+    pub fn sign_extend(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::SignExtend(op, ty), range)
+    }
+
+    pub fn zero_extend(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::ZeroExtend(op, ty), range)
+    }
+
+    pub fn float_cast(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::FloatCast(op, ty), range)
+    }
+
+    pub fn float_to_int(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::FloatToInt(op, ty), range)
+    }
+
+    pub fn int_to_float(&mut self, op: OpId, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::IntToFloat(op, ty), range)
+    }
+
+    pub fn load(&mut self, location: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Load(location), range)
+    }
+
+    pub fn store(&mut self, location: OpId, value: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Store { location, value }, range)
+    }
+
+    pub fn address_of_static(&mut self, statik: StaticId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::AddressOfStatic(statik), range)
+    }
+
+    pub fn pointer(&mut self, op: OpId, is_mut: bool, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Pointer { op, is_mut }, range)
+    }
+
+    pub fn strukt(&mut self, id: StructId, fields: SmallVec<[OpId; 2]>, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Struct { fields, id }, range)
+    }
+
+    pub fn enuum(&mut self, id: EnumId, variants: SmallVec<[OpId; 2]>, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Enum { variants, id }, range)
+    }
+
+    pub fn struct_lit(&mut self, id: StructId, fields: SmallVec<[OpId; 2]>, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::StructLit { fields, id }, range)
+    }
+
+    pub fn direct_field_access(&mut self, val: OpId, index: usize, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::DirectFieldAccess { val, index }, range)
+    }
+
+    pub fn indirect_field_access(&mut self, val: OpId, index: usize, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::IndirectFieldAccess { val, index }, range)
+    }
+
+    pub fn variant(&mut self, enuum: EnumId, index: usize, payload: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Variant { enuum, index, payload }, range)
+    }
+
+    pub fn discriminant_access(&mut self, val: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::DiscriminantAccess { val }, range)
+    }
+
+    pub fn element_access(&mut self, val: OpId, index: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::ElementAccess { val, index }, range)
+    }
+
+    pub fn element_address(&mut self, ptr: OpId, index: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::ElementAddress { ptr, index }, range)
+    }
+
+    pub fn make_slice(&mut self, ptr: OpId, len: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::MakeSlice { ptr, len }, range)
+    }
+
+    pub fn slice_len(&mut self, slice: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::SliceLen(slice), range)
+    }
+
+    pub fn ret(&mut self, val: OpId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Ret(val), range)
+    }
+
+    pub fn br(&mut self, bb: BlockId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::Br(bb), range)
+    }
+
+    pub fn cond_br(&mut self, condition: OpId, true_bb: BlockId, false_bb: BlockId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::CondBr { condition, true_bb, false_bb }, range)
+    }
+
+    pub fn switch_br(&mut self, scrutinee: OpId, cases: Vec<SwitchCase>, catch_all_bb: BlockId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::SwitchBr { scrutinee, cases, catch_all_bb }, range)
+    }
+
+    pub fn generic_param(&mut self, id: GenericParamId, range: SourceRange) -> Result<OpId, BuildError> {
+        self.push(Instr::GenericParam(id), range)
+    }
+
+    pub fn parameter(&mut self, ty: Type, range: SourceRange) -> Result<OpId, BuildError> {
+        let block = self.block.ok_or(BuildError::NoInsertionBlock)?;
+        let is_entry = self.code.mir_code.functions[self.func].blocks.first() == Some(&block);
+        let only_params_so_far = self.code.blocks[block].ops.iter().all(|&op| {
+            matches!(self.code.ops[op].as_mir_instr(), Some(Instr::Void) | Some(Instr::Parameter(_)))
+        });
+        if !is_entry || !only_params_so_far {
+            return Err(BuildError::MisplacedParameter);
+        }
+        self.push(Instr::Parameter(ty), range)
+    }
+
+    pub fn phi(&mut self, incoming: SmallVec<[PhiEdge; 2]>, range: SourceRange) -> Result<OpId, BuildError> {
+        let block = self.block.ok_or(BuildError::NoInsertionBlock)?;
+        let only_phis_so_far = self.code.blocks[block].ops.iter().all(|&op| {
+            matches!(self.code.ops[op].as_mir_instr(), Some(Instr::Phi { .. }))
+        });
+        if !only_phis_so_far {
+            return Err(BuildError::MisplacedPhi);
+        }
+        self.push(Instr::Phi { incoming }, range)
+    }
+
+    /// Adds an incoming edge to an existing phi, typically once the value flowing in from `bb` is known
+    pub fn add_phi_incoming(&mut self, phi: OpId, bb: BlockId, value: OpId) -> Result<(), BuildError> {
+        let mut instr = match self.code.ops[phi].as_mir_instr() {
+            Some(instr @ Instr::Phi { .. }) => instr.clone(),
+            _ => return Err(BuildError::NotPhi(phi)),
+        };
+        if let Instr::Phi { incoming } = &mut instr {
+            incoming.push(PhiEdge { bb, value });
+        }
+        self.code.set_mir_instr(phi, instr);
+        Ok(())
+    }
+}
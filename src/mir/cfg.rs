@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+
+use smallvec::SmallVec;
+
+use crate::mir::{Function, Instr};
+use crate::{Code, BlockId};
+
+/// The control-flow graph of a single MIR function
+#[derive(Debug)]
+pub struct Cfg {
+    entry: BlockId,
+    blocks: Vec<BlockId>,
+    successors: HashMap<BlockId, SmallVec<[BlockId; 2]>>,
+    predecessors: HashMap<BlockId, SmallVec<[BlockId; 2]>>,
+    postorder: Vec<BlockId>,
+    reverse_postorder: Vec<BlockId>,
+    reachable: HashSet<BlockId>,
+}
+
+impl Cfg {
+    pub fn new(code: &Code, func: &Function) -> Cfg {
+        let mut successors = HashMap::new();
+        let mut predecessors: HashMap<BlockId, SmallVec<[BlockId; 2]>> = HashMap::new();
+        for &block in &func.blocks {
+            predecessors.entry(block).or_default();
+        }
+        for &block in &func.blocks {
+            let mut block_successors: SmallVec<[BlockId; 2]> = SmallVec::new();
+            if let Some(terminator) = code.terminator(block) {
+                for target in terminator.successors() {
+                    if !block_successors.contains(&target) {
+                        block_successors.push(target);
+                        predecessors.entry(target).or_default().push(block);
+                    }
+                }
+            }
+            successors.insert(block, block_successors);
+        }
+
+        // Iterative depth-first search from the entry block
+        let entry = func.blocks[0];
+        let mut postorder = Vec::new();
+        let mut reachable = HashSet::new();
+        reachable.insert(entry);
+        let mut stack = vec![(entry, 0)];
+        while let Some(&(block, next_successor)) = stack.last() {
+            match successors.get(&block).and_then(|successors| successors.get(next_successor)) {
+                Some(&successor) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    if reachable.insert(successor) {
+                        stack.push((successor, 0));
+                    }
+                },
+                None => {
+                    postorder.push(block);
+                    stack.pop();
+                },
+            }
+        }
+        let reverse_postorder = postorder.iter().rev().copied().collect();
+
+        Cfg {
+            entry,
+            blocks: func.blocks.clone(),
+            successors,
+            predecessors,
+            postorder,
+            reverse_postorder,
+            reachable,
+        }
+    }
+
+    pub fn entry(&self) -> BlockId { self.entry }
+
+    /// All blocks in the function, in the order of `Function::blocks`
+    pub fn blocks(&self) -> &[BlockId] { &self.blocks }
+
+    pub fn successors(&self, block: BlockId) -> &[BlockId] {
+        &self.successors[&block]
+    }
+
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[&block]
+    }
+
+    /// Reachable blocks in postorder
+    pub fn postorder(&self) -> &[BlockId] { &self.postorder }
+
+    /// Reachable blocks in reverse postorder
+    pub fn reverse_postorder(&self) -> &[BlockId] { &self.reverse_postorder }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.reachable.contains(&block)
+    }
+
+    /// Blocks that can't be reached from the entry block, in the order of `Function::blocks`
+    pub fn unreachable_blocks(&self) -> Vec<BlockId> {
+        self.blocks.iter()
+            .copied()
+            .filter(|block| !self.is_reachable(*block))
+            .collect()
+    }
+
+    /// Blocks that end in a `Ret`
+    pub fn exits(&self, code: &Code) -> Vec<BlockId> {
+        self.blocks.iter()
+            .copied()
+            .filter(|&block| matches!(code.terminator(block), Some(Instr::Ret(_))))
+            .collect()
+    }
+}
+
+impl Code {
+    /// Returns the last instr of `block` if it is a terminator
+    pub fn terminator(&self, block: BlockId) -> Option<&Instr> {
+        let &last = self.blocks[block].ops.last()?;
+        self.ops[last].as_mir_instr().filter(|instr| instr.is_terminator())
+    }
+}
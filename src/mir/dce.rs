@@ -0,0 +1,94 @@
+//! Dead code elimination: removes blocks that can't be reached from the entry block, and
+//! side-effect-free instrs whose values are never used.
+
+use std::collections::HashSet;
+
+use crate::mir::cfg::Cfg;
+use crate::mir::{FuncId, VOID_INSTR};
+use crate::{Code, Op, BlockId, OpId};
+
+impl Code {
+    /// Removes the blocks of `func` that can't be reached from its entry block, along with their
+    /// ops and the phi edges coming from them. Returns the number of blocks and ops removed.
+    pub fn remove_unreachable_blocks(&mut self, func: FuncId) -> usize {
+        let function = &self.mir_code.functions[func];
+        if function.blocks.is_empty() {
+            return 0;
+        }
+        let cfg = Cfg::new(self, function);
+        let unreachable = cfg.unreachable_blocks();
+        let num_removed = unreachable.len();
+
+        // Reachable blocks may have phis with incoming edges from the unreachable ones
+        for &block in &unreachable {
+            for &successor in cfg.successors(block) {
+                if !cfg.is_reachable(successor) {
+                    continue;
+                }
+                self.remove_phi_edges(successor, block);
+            }
+        }
+        let unreachable_blocks: HashSet<BlockId> = unreachable.iter().copied().collect();
+        self.mir_code.functions[func].blocks.retain(|block| !unreachable_blocks.contains(block));
+
+        let mut ops = Vec::new();
+        for &block in &unreachable {
+            // `VOID_INSTR` itself is never erased
+            self.blocks[block].ops.retain(|&op| op != VOID_INSTR);
+            ops.extend(self.blocks[block].ops.iter().map(|&op| (block, op)));
+        }
+        num_removed + self.erase_unused_ops(ops)
+    }
+
+    /// Removes unreachable blocks from `func`, and then every instr that neither has side effects
+    /// nor is transitively used by one that does. Returns the number of blocks and ops removed.
+    pub fn eliminate_dead_code(&mut self, func: FuncId) -> usize {
+        let num_removed = self.remove_unreachable_blocks(func);
+
+        // Mark every instr with side effects live, along with everything they transitively use
+        let blocks = self.mir_code.functions[func].blocks.clone();
+        let mut live = HashSet::new();
+        live.insert(VOID_INSTR);
+        let mut worklist = Vec::new();
+        for &block in &blocks {
+            for &op in &self.blocks[block].ops {
+                let is_root = match &self.ops[op] {
+                    Op::MirInstr(instr) => instr.has_side_effects(),
+                    Op::HirItem(_) => true,
+                };
+                if is_root && live.insert(op) {
+                    worklist.push(op);
+                }
+            }
+        }
+        while let Some(op) = worklist.pop() {
+            if let Some(instr) = self.ops[op].as_mir_instr() {
+                for operand in instr.operands() {
+                    if live.insert(operand) {
+                        worklist.push(operand);
+                    }
+                }
+            }
+        }
+        let mut dead: Vec<(BlockId, OpId)> = Vec::new();
+        for &block in &blocks {
+            dead.extend(self.blocks[block].ops.iter().filter(|op| !live.contains(op)).map(|&op| (block, op)));
+        }
+
+        num_removed + self.erase_unused_ops(dead)
+    }
+
+    /// Erases `ops`, which may use each other but must not be used by any op outside of them.
+    /// Returns the number of ops erased.
+    fn erase_unused_ops(&mut self, ops: Vec<(BlockId, OpId)>) -> usize {
+        // Erasing an op requires it to have no uses, so detach them all before erasing any
+        for &(_, op) in &ops {
+            self.replace_all_uses(op, VOID_INSTR);
+        }
+        let num_erased = ops.len();
+        for (block, op) in ops {
+            self.erase_op(block, op).unwrap();
+        }
+        num_erased
+    }
+}
@@ -0,0 +1,273 @@
+//! Dominator and post-dominator trees, computed with the algorithm from "A Simple, Fast Dominance
+//! Algorithm" by Cooper, Harvey and Kennedy.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::mir::cfg::Cfg;
+use crate::mir::{Function, VOID_INSTR};
+use crate::{Code, BlockId, OpId};
+
+/// Computes immediate dominators of a graph whose nodes are numbered in reverse postorder, with
+/// node 0 as the root
+fn compute_idoms(predecessors: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let mut idoms = vec![None; predecessors.len()];
+    idoms[0] = Some(0);
+    let intersect = |idoms: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while a > b {
+                a = idoms[a].unwrap();
+            }
+            while b > a {
+                b = idoms[b].unwrap();
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in 1..predecessors.len() {
+            let mut new_idom = None;
+            for &pred in &predecessors[node] {
+                if idoms[pred].is_some() {
+                    new_idom = Some(match new_idom {
+                        Some(idom) => intersect(&idoms, pred, idom),
+                        None => pred,
+                    });
+                }
+            }
+            if new_idom.is_some() && new_idom != idoms[node] {
+                idoms[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idoms
+}
+
+/// A dominator tree, or a post-dominator tree, over the blocks of one function
+#[derive(Debug)]
+pub struct DomTree {
+    is_post: bool,
+    idoms: HashMap<BlockId, BlockId>,
+    children: HashMap<BlockId, Vec<BlockId>>,
+    /// Blocks with no immediate dominator. For a dominator tree this is the entry block; for a
+    /// post-dominator tree it is every block immediately post-dominated by the virtual exit, which
+    /// includes every block that ends in a `Ret`.
+    roots: Vec<BlockId>,
+    /// Preorder and postorder numbers of each block in the tree, for constant-time dominance queries
+    intervals: HashMap<BlockId, (usize, usize)>,
+    /// The block and index within the block of each op in the function
+    op_positions: HashMap<OpId, (BlockId, usize)>,
+}
+
+impl DomTree {
+    /// Builds the dominator tree of the blocks reachable from the entry block
+    pub fn new(code: &Code, func: &Function, cfg: &Cfg) -> DomTree {
+        let nodes = cfg.reverse_postorder();
+        let indices: HashMap<BlockId, usize> = nodes.iter().enumerate().map(|(i, &block)| (block, i)).collect();
+        let predecessors: Vec<Vec<usize>> = nodes.iter()
+            .map(|&block| cfg.predecessors(block).iter().filter_map(|pred| indices.get(pred).copied()).collect())
+            .collect();
+        let idoms = compute_idoms(&predecessors);
+        let mut idom_map = HashMap::new();
+        for (i, idom) in idoms.iter().enumerate().skip(1) {
+            if let &Some(idom) = idom {
+                idom_map.insert(nodes[i], nodes[idom]);
+            }
+        }
+        DomTree::from_idoms(code, func, false, idom_map, vec![cfg.entry()])
+    }
+
+    /// Builds the post-dominator tree of the blocks that can reach a `Ret`
+    pub fn new_post(code: &Code, func: &Function, cfg: &Cfg) -> DomTree {
+        // Node 0 is a virtual exit block that succeeds every block ending in a `Ret`
+        let exits = cfg.exits(code);
+        let mut postorder = Vec::new();
+        let mut visited: HashSet<BlockId> = exits.iter().copied().collect();
+        for &exit in &exits {
+            let mut stack = vec![(exit, 0)];
+            while let Some(&(block, next_pred)) = stack.last() {
+                match cfg.predecessors(block).get(next_pred) {
+                    Some(&pred) => {
+                        stack.last_mut().unwrap().1 += 1;
+                        if visited.insert(pred) {
+                            stack.push((pred, 0));
+                        }
+                    },
+                    None => {
+                        postorder.push(block);
+                        stack.pop();
+                    },
+                }
+            }
+        }
+        let nodes: Vec<BlockId> = postorder.into_iter().rev().collect();
+        let indices: HashMap<BlockId, usize> = nodes.iter().enumerate().map(|(i, &block)| (block, i + 1)).collect();
+        let mut predecessors = vec![Vec::new()];
+        for &block in &nodes {
+            let mut preds: Vec<usize> = cfg.successors(block).iter().filter_map(|succ| indices.get(succ).copied()).collect();
+            if exits.contains(&block) {
+                preds.push(0);
+            }
+            predecessors.push(preds);
+        }
+        let idoms = compute_idoms(&predecessors);
+        let mut idom_map = HashMap::new();
+        let mut roots = Vec::new();
+        for (i, idom) in idoms.iter().enumerate().skip(1) {
+            match *idom {
+                // A block that branches to more than one exit is only post-dominated by the
+                // virtual exit, just like the exits themselves
+                Some(0) => roots.push(nodes[i - 1]),
+                None => {},
+                Some(idom) => {
+                    idom_map.insert(nodes[i - 1], nodes[idom - 1]);
+                },
+            }
+        }
+        // Order the roots like the function's blocks, so that the tree is deterministic
+        roots.sort_by_key(|root| func.blocks.iter().position(|block| block == root));
+        DomTree::from_idoms(code, func, true, idom_map, roots)
+    }
+
+    fn from_idoms(code: &Code, func: &Function, is_post: bool, idoms: HashMap<BlockId, BlockId>, roots: Vec<BlockId>) -> DomTree {
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        // Iterate in function order so that children are deterministic
+        for block in &func.blocks {
+            if let Some(&idom) = idoms.get(block) {
+                children.entry(idom).or_default().push(*block);
+            }
+        }
+
+        let mut intervals = HashMap::new();
+        let mut counter = 0;
+        for &root in &roots {
+            let mut stack = vec![(root, 0)];
+            intervals.insert(root, (counter, 0));
+            counter += 1;
+            while let Some(&(block, next_child)) = stack.last() {
+                match children.get(&block).and_then(|children| children.get(next_child)) {
+                    Some(&child) => {
+                        stack.last_mut().unwrap().1 += 1;
+                        intervals.insert(child, (counter, 0));
+                        counter += 1;
+                        stack.push((child, 0));
+                    },
+                    None => {
+                        intervals.get_mut(&block).unwrap().1 = counter;
+                        counter += 1;
+                        stack.pop();
+                    },
+                }
+            }
+        }
+
+        let mut op_positions = HashMap::new();
+        for &block in &func.blocks {
+            for (i, &op) in code.blocks[block].ops.iter().enumerate() {
+                op_positions.insert(op, (block, i));
+            }
+        }
+
+        DomTree { is_post, idoms, children, roots, intervals, op_positions }
+    }
+
+    pub fn is_post(&self) -> bool { self.is_post }
+
+    /// Returns the immediate (post-)dominator of `block`, if it has one
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idoms.get(&block).copied()
+    }
+
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        self.children.get(&block).map(|children| children.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn roots(&self) -> &[BlockId] { &self.roots }
+
+    /// Whether `block` is in the tree. Blocks are left out of a dominator tree if they are
+    /// unreachable, and out of a post-dominator tree if they can't reach a `Ret`.
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.intervals.contains_key(&block)
+    }
+
+    /// Whether `a` (post-)dominates `b`. Every block in the tree dominates itself.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        match (self.intervals.get(&a), self.intervals.get(&b)) {
+            (Some(&(a_pre, a_post)), Some(&(b_pre, b_post))) => a_pre <= b_pre && b_post <= a_post,
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Whether the op `a` (post-)dominates the op `b`. `VOID_INSTR` dominates everything, and
+    /// every op dominates itself.
+    pub fn op_dominates(&self, a: OpId, b: OpId) -> bool {
+        if a == VOID_INSTR {
+            return true;
+        }
+        match (self.op_positions.get(&a), self.op_positions.get(&b)) {
+            (Some(&(a_block, a_index)), Some(&(b_block, b_index))) => if a_block == b_block {
+                if self.is_post { a_index >= b_index } else { a_index <= b_index }
+            } else {
+                self.dominates(a_block, b_block)
+            },
+            _ => false,
+        }
+    }
+
+    /// Blocks in the tree in preorder
+    pub fn preorder(&self) -> Vec<BlockId> {
+        let mut blocks: Vec<BlockId> = self.intervals.keys().copied().collect();
+        blocks.sort_by_key(|block| self.intervals[block].0);
+        blocks
+    }
+}
+
+/// The (post-)dominance frontier of each block
+#[derive(Debug)]
+pub struct DominanceFrontiers {
+    frontiers: HashMap<BlockId, Vec<BlockId>>,
+}
+
+impl DominanceFrontiers {
+    /// Computes dominance frontiers from a dominator tree, or post-dominance frontiers from a
+    /// post-dominator tree
+    pub fn new(cfg: &Cfg, dom: &DomTree) -> DominanceFrontiers {
+        let mut frontiers: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for &block in cfg.blocks() {
+            if !dom.contains(block) {
+                continue;
+            }
+            let preds = if dom.is_post() { cfg.successors(block) } else { cfg.predecessors(block) };
+            let preds: Vec<BlockId> = preds.iter().copied().filter(|&pred| dom.contains(pred)).collect();
+            // For post-dominance, a root also has the virtual exit as a successor
+            let is_join = preds.len() >= 2 || (dom.is_post() && dom.roots().contains(&block) && !preds.is_empty());
+            if !is_join {
+                continue;
+            }
+            for pred in preds {
+                let mut runner = Some(pred);
+                while let Some(current) = runner {
+                    if Some(current) == dom.idom(block) {
+                        break;
+                    }
+                    let frontier = frontiers.entry(current).or_default();
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    runner = dom.idom(current);
+                }
+            }
+        }
+        DominanceFrontiers { frontiers }
+    }
+
+    pub fn frontier(&self, block: BlockId) -> &[BlockId] {
+        self.frontiers.get(&block).map(|frontier| frontier.as_slice()).unwrap_or(&[])
+    }
+}
@@ -0,0 +1,280 @@
+//! Evaluation of arithmetic intrinsics and casts on constants.
+//!
+//! Integer constants are stored in `Const::Int::lit` as their low `bit_width` bits, with all higher
+//! bits zeroed. Signed values are stored in two's complement, so an `i8` holding -1 has a `lit` of
+//! 0xFF. Every function in this module accepts and produces values in that form.
+
+use crate::arch::Arch;
+use crate::hir::Intrinsic;
+use crate::mir::{Const, Instr};
+use crate::ty::{Type, FloatWidth};
+use crate::OpId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    DivideByZero,
+    /// Signed division or remainder of the minimum value by -1
+    Overflow,
+    /// A float was NaN or out of range of the integer type it was converted to
+    FloatToIntOutOfRange,
+    /// The operands don't have the types the operation expects
+    InvalidOperands,
+    /// The operation depends on something other than its operands, like memory or IO
+    NotConstant,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CastKind {
+    Reinterpret,
+    Truncate,
+    SignExtend,
+    ZeroExtend,
+    FloatCast,
+    FloatToInt,
+    IntToFloat,
+}
+
+impl CastKind {
+    /// If `instr` is a cast, returns its kind, operand and destination type
+    pub fn of(instr: &Instr) -> Option<(CastKind, OpId, &Type)> {
+        let cast = match instr {
+            Instr::Reinterpret(op, ty) => (CastKind::Reinterpret, *op, ty),
+            Instr::Truncate(op, ty) => (CastKind::Truncate, *op, ty),
+            Instr::SignExtend(op, ty) => (CastKind::SignExtend, *op, ty),
+            Instr::ZeroExtend(op, ty) => (CastKind::ZeroExtend, *op, ty),
+            Instr::FloatCast(op, ty) => (CastKind::FloatCast, *op, ty),
+            Instr::FloatToInt(op, ty) => (CastKind::FloatToInt, *op, ty),
+            Instr::IntToFloat(op, ty) => (CastKind::IntToFloat, *op, ty),
+            _ => return None,
+        };
+        Some(cast)
+    }
+}
+
+/// Returns the bit width and signedness of integer and pointer types
+pub fn int_info(ty: &Type, arch: Arch) -> Option<(usize, bool)> {
+    match ty {
+        &Type::Int { width, is_signed } => Some((width.bit_width(arch), is_signed)),
+        Type::Pointer { .. } => Some((arch.pointer_size(), false)),
+        _ => None,
+    }
+}
+
+/// Keeps only the low `bits` bits of `lit`
+pub fn truncate(lit: u64, bits: usize) -> u64 {
+    if bits >= 64 {
+        lit
+    } else {
+        lit & ((1 << bits) - 1)
+    }
+}
+
+/// Interprets the low `bits` bits of `lit` as a two's complement integer
+pub fn sign_extend(lit: u64, bits: usize) -> i64 {
+    if bits >= 64 {
+        lit as i64
+    } else {
+        let shift = 64 - bits;
+        ((lit << shift) as i64) >> shift
+    }
+}
+
+fn round_float(lit: f64, ty: &Type) -> f64 {
+    match ty {
+        Type::Float(FloatWidth::W32) => lit as f32 as f64,
+        _ => lit,
+    }
+}
+
+fn eval_int_binary(intr: Intrinsic, a: u64, b: u64, ty: &Type, arch: Arch) -> Result<Const, EvalError> {
+    let (bits, is_signed) = int_info(ty, arch).ok_or(EvalError::InvalidOperands)?;
+    let (a, b) = (truncate(a, bits), truncate(b, bits));
+    let (sa, sb) = (sign_extend(a, bits), sign_extend(b, bits));
+    let int = |lit: u64| Ok(Const::Int { lit: truncate(lit, bits), ty: *ty });
+    let signed_min = sign_extend(1 << (bits - 1), bits);
+    let check_division = || {
+        if b == 0 {
+            Err(EvalError::DivideByZero)
+        } else if is_signed && sb == -1 && sa == signed_min {
+            Err(EvalError::Overflow)
+        } else {
+            Ok(())
+        }
+    };
+    let ordering = if is_signed { sa.cmp(&sb) } else { a.cmp(&b) };
+    match intr {
+        Intrinsic::Add => int(a.wrapping_add(b)),
+        Intrinsic::Sub => int(a.wrapping_sub(b)),
+        Intrinsic::Mult => int(a.wrapping_mul(b)),
+        Intrinsic::Div => {
+            check_division()?;
+            int(if is_signed { (sa / sb) as u64 } else { a / b })
+        },
+        Intrinsic::Mod => {
+            check_division()?;
+            int(if is_signed { (sa % sb) as u64 } else { a % b })
+        },
+        Intrinsic::BitwiseAnd => int(a & b),
+        Intrinsic::BitwiseOr => int(a | b),
+        Intrinsic::Eq => Ok(Const::Bool(a == b)),
+        Intrinsic::NotEq => Ok(Const::Bool(a != b)),
+        Intrinsic::Less => Ok(Const::Bool(ordering.is_lt())),
+        Intrinsic::LessOrEq => Ok(Const::Bool(ordering.is_le())),
+        Intrinsic::Greater => Ok(Const::Bool(ordering.is_gt())),
+        Intrinsic::GreaterOrEq => Ok(Const::Bool(ordering.is_ge())),
+        _ => Err(EvalError::InvalidOperands),
+    }
+}
+
+fn eval_float_binary(intr: Intrinsic, a: f64, b: f64, ty: &Type) -> Result<Const, EvalError> {
+    let float = |lit: f64| Ok(Const::Float { lit: round_float(lit, ty), ty: *ty });
+    match intr {
+        Intrinsic::Add => float(a + b),
+        Intrinsic::Sub => float(a - b),
+        Intrinsic::Mult => float(a * b),
+        Intrinsic::Div => float(a / b),
+        Intrinsic::Mod => float(a % b),
+        Intrinsic::Eq => Ok(Const::Bool(a == b)),
+        Intrinsic::NotEq => Ok(Const::Bool(a != b)),
+        Intrinsic::Less => Ok(Const::Bool(a < b)),
+        Intrinsic::LessOrEq => Ok(Const::Bool(a <= b)),
+        Intrinsic::Greater => Ok(Const::Bool(a > b)),
+        Intrinsic::GreaterOrEq => Ok(Const::Bool(a >= b)),
+        _ => Err(EvalError::InvalidOperands),
+    }
+}
+
+/// Evaluates `intr` on constant `arguments`
+pub fn eval_intrinsic(intr: Intrinsic, arguments: &[Const], arch: Arch) -> Result<Const, EvalError> {
+    let named_ty = match intr {
+        Intrinsic::I8 => Some(Type::i8()),
+        Intrinsic::I16 => Some(Type::i16()),
+        Intrinsic::I32 => Some(Type::i32()),
+        Intrinsic::I64 => Some(Type::i64()),
+        Intrinsic::Isize => Some(Type::isize()),
+        Intrinsic::U8 => Some(Type::u8()),
+        Intrinsic::U16 => Some(Type::u16()),
+        Intrinsic::U32 => Some(Type::u32()),
+        Intrinsic::U64 => Some(Type::u64()),
+        Intrinsic::Usize => Some(Type::usize()),
+        Intrinsic::F32 => Some(Type::f32()),
+        Intrinsic::F64 => Some(Type::f64()),
+        Intrinsic::Never => Some(Type::Never),
+        Intrinsic::Bool => Some(Type::Bool),
+        Intrinsic::Void => Some(Type::Void),
+        Intrinsic::Ty => Some(Type::Ty),
+        Intrinsic::Module => Some(Type::Mod),
+        _ => None,
+    };
+    if let Some(named_ty) = named_ty {
+        return Ok(Const::Ty(named_ty));
+    }
+    if matches!(
+        intr,
+        Intrinsic::Panic | Intrinsic::Print | Intrinsic::Malloc | Intrinsic::Free | Intrinsic::PrintType
+            | Intrinsic::SizeOf | Intrinsic::StrideOf | Intrinsic::AlignOf | Intrinsic::OffsetOf
+    ) {
+        return Err(EvalError::NotConstant);
+    }
+
+    match arguments {
+        [Const::Int { lit, ty: arg_ty }] => match intr {
+            Intrinsic::Neg => {
+                let (bits, _) = int_info(arg_ty, arch).ok_or(EvalError::InvalidOperands)?;
+                Ok(Const::Int { lit: truncate(lit.wrapping_neg(), bits), ty: *arg_ty })
+            },
+            Intrinsic::Pos => Ok(arguments[0].clone()),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        [Const::Float { lit, ty: arg_ty }] => match intr {
+            Intrinsic::Neg => Ok(Const::Float { lit: -lit, ty: *arg_ty }),
+            Intrinsic::Pos => Ok(arguments[0].clone()),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        &[Const::Bool(val)] => match intr {
+            Intrinsic::LogicalNot => Ok(Const::Bool(!val)),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        [Const::Int { lit: a, ty: arg_ty }, Const::Int { lit: b, .. }] => eval_int_binary(intr, *a, *b, arg_ty, arch),
+        [Const::Float { lit: a, ty: arg_ty }, Const::Float { lit: b, .. }] => eval_float_binary(intr, *a, *b, arg_ty),
+        &[Const::Bool(a), Const::Bool(b)] => match intr {
+            Intrinsic::LogicalAnd | Intrinsic::BitwiseAnd => Ok(Const::Bool(a && b)),
+            Intrinsic::LogicalOr | Intrinsic::BitwiseOr => Ok(Const::Bool(a || b)),
+            Intrinsic::Eq => Ok(Const::Bool(a == b)),
+            Intrinsic::NotEq => Ok(Const::Bool(a != b)),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        [a, b] => match intr {
+            Intrinsic::Eq => Ok(Const::Bool(a == b)),
+            Intrinsic::NotEq => Ok(Const::Bool(a != b)),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        _ => Err(EvalError::InvalidOperands),
+    }
+}
+
+/// Evaluates a cast of `val` to `ty`
+pub fn eval_cast(kind: CastKind, val: &Const, ty: &Type, arch: Arch) -> Result<Const, EvalError> {
+    let dest_int = int_info(ty, arch);
+    let int = |lit: u64| {
+        let (bits, _) = dest_int.ok_or(EvalError::InvalidOperands)?;
+        Ok(Const::Int { lit: truncate(lit, bits), ty: *ty })
+    };
+    let float = |lit: f64| match ty {
+        Type::Float(_) => Ok(Const::Float { lit: round_float(lit, ty), ty: *ty }),
+        _ => Err(EvalError::InvalidOperands),
+    };
+    match (kind, val) {
+        (CastKind::Truncate, &Const::Int { lit, .. }) | (CastKind::ZeroExtend, &Const::Int { lit, .. }) => int(lit),
+        (CastKind::ZeroExtend, &Const::Bool(val)) => int(val as u64),
+        (CastKind::SignExtend, Const::Int { lit, ty: src_ty }) => {
+            let (bits, _) = int_info(src_ty, arch).ok_or(EvalError::InvalidOperands)?;
+            int(sign_extend(*lit, bits) as u64)
+        },
+        (CastKind::FloatCast, &Const::Float { lit, .. }) => float(lit),
+        (CastKind::IntToFloat, Const::Int { lit, ty: src_ty }) => {
+            let (bits, is_signed) = int_info(src_ty, arch).ok_or(EvalError::InvalidOperands)?;
+            if is_signed {
+                float(sign_extend(*lit, bits) as f64)
+            } else {
+                float(*lit as f64)
+            }
+        },
+        (CastKind::FloatToInt, &Const::Float { lit, .. }) => {
+            let (bits, is_signed) = dest_int.ok_or(EvalError::InvalidOperands)?;
+            let lit = lit.trunc();
+            let (min, max) = if is_signed {
+                (-(2f64.powi(bits as i32 - 1)), 2f64.powi(bits as i32 - 1))
+            } else {
+                (0.0, 2f64.powi(bits as i32))
+            };
+            if lit.is_nan() || lit < min || lit >= max {
+                Err(EvalError::FloatToIntOutOfRange)
+            } else if is_signed {
+                int(lit as i64 as u64)
+            } else {
+                int(lit as u64)
+            }
+        },
+        (CastKind::Reinterpret, Const::Int { lit, ty: src_ty }) => {
+            let (src_bits, _) = int_info(src_ty, arch).ok_or(EvalError::InvalidOperands)?;
+            match ty {
+                Type::Float(FloatWidth::W32) if src_bits == 32 => float(f32::from_bits(*lit as u32) as f64),
+                Type::Float(FloatWidth::W64) if src_bits == 64 => float(f64::from_bits(*lit)),
+                _ => match dest_int {
+                    Some((bits, _)) if bits == src_bits => int(*lit),
+                    _ => Err(EvalError::InvalidOperands),
+                },
+            }
+        },
+        (CastKind::Reinterpret, Const::Float { lit, ty: src_ty }) => match (src_ty, dest_int) {
+            (Type::Float(FloatWidth::W32), Some((32, _))) => int((*lit as f32).to_bits() as u64),
+            (Type::Float(FloatWidth::W64), Some((64, _))) => int(lit.to_bits()),
+            (Type::Float(_), _) if ty == src_ty => Ok(val.clone()),
+            _ => Err(EvalError::InvalidOperands),
+        },
+        (CastKind::Reinterpret, &Const::Str { id, .. }) if matches!(ty, Type::Pointer { .. }) => Ok(Const::Str { id, ty: *ty }),
+        (CastKind::Reinterpret, _) if val.ty() == *ty => Ok(val.clone()),
+        _ => Err(EvalError::InvalidOperands),
+    }
+}
@@ -0,0 +1,85 @@
+//! Constant folding of intrinsics and casts whose operands are all `Const` instrs.
+
+use smallvec::{SmallVec, smallvec};
+
+use crate::arch::Arch;
+use crate::hir::Intrinsic;
+use crate::mir::cfg::Cfg;
+use crate::mir::eval::{self, CastKind, EvalError};
+use crate::mir::{Const, FuncId, Instr};
+use crate::source_info::SourceRange;
+use crate::ty::Type;
+use crate::{Code, OpId};
+
+/// An instr that would trap if executed, like a division by zero. The instr is left unfolded.
+#[derive(Debug)]
+pub struct FoldError {
+    pub kind: EvalError,
+    pub op: OpId,
+    /// The entry in `MirCode::source_ranges` for `op`, if there is one
+    pub range: Option<SourceRange>,
+}
+
+impl Code {
+    fn const_operand(&self, op: OpId) -> Option<&Const> {
+        match self.ops[op].as_mir_instr() {
+            Some(Instr::Const(konst)) => Some(konst),
+            _ => None,
+        }
+    }
+
+    /// Evaluates `instr` if it is a foldable intrinsic or cast with constant operands
+    fn fold_instr(&self, instr: &Instr, arch: Arch) -> Option<Result<Const, EvalError>> {
+        if let Some((kind, op, ty)) = CastKind::of(instr) {
+            let val = self.const_operand(op)?;
+            return Some(eval::eval_cast(kind, val, ty, arch));
+        }
+        let (intr, arguments, ty): (Intrinsic, SmallVec<[OpId; 2]>, Type) = match instr {
+            &Instr::LogicalNot(op) => (Intrinsic::LogicalNot, smallvec![op], Type::Bool),
+            Instr::Intrinsic { intr, arguments, ty } => (*intr, arguments.clone(), *ty),
+            _ => return None,
+        };
+        // Layout intrinsics take types, which may come from `Struct` and `Enum` instrs
+        let arguments = arguments.iter()
+            .map(|&arg| self.const_operand(arg).cloned().or_else(|| self.type_operand(arg).map(Const::Ty)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(self.eval_intrinsic(intr, &arguments, &ty, arch))
+    }
+
+    /// Replaces each intrinsic and cast in `func` whose operands are all constants with the
+    /// resulting `Const`. Returns the number of instrs folded, along with the instrs that would
+    /// trap and so were left alone.
+    pub fn fold_constants(&mut self, func: FuncId, arch: Arch) -> (usize, Vec<FoldError>) {
+        let function = &self.mir_code.functions[func];
+        if function.blocks.is_empty() {
+            return (0, Vec::new());
+        }
+        // Visiting blocks in reverse postorder means operands are folded before their uses
+        let cfg = Cfg::new(self, function);
+        let mut num_folded = 0;
+        let mut errors = Vec::new();
+        for &block in cfg.reverse_postorder() {
+            for i in 0..self.blocks[block].ops.len() {
+                let op = self.blocks[block].ops[i];
+                let result = match self.ops[op].as_mir_instr() {
+                    Some(instr) => self.fold_instr(instr, arch),
+                    None => None,
+                };
+                match result {
+                    Some(Ok(konst)) => {
+                        self.set_mir_instr(op, Instr::Const(konst));
+                        num_folded += 1;
+                    },
+                    Some(Err(kind @ EvalError::DivideByZero))
+                        | Some(Err(kind @ EvalError::Overflow))
+                        | Some(Err(kind @ EvalError::FloatToIntOutOfRange)) => {
+                        let range = self.mir_code.source_ranges.get(&op).copied();
+                        errors.push(FoldError { kind, op, range });
+                    },
+                    Some(Err(EvalError::InvalidOperands)) | Some(Err(EvalError::NotConstant)) | None => {},
+                }
+            }
+        }
+        (num_folded, errors)
+    }
+}
@@ -0,0 +1,73 @@
+//! Global value numbering: pure instrs that compute the same value as a dominating instr are
+//! replaced by it.
+
+use std::collections::HashMap;
+
+use crate::hir::Intrinsic;
+use crate::mir::cfg::Cfg;
+use crate::mir::dom::DomTree;
+use crate::mir::{FuncId, Instr};
+use crate::{Code, OpId};
+
+fn is_commutative(intr: Intrinsic) -> bool {
+    matches!(
+        intr,
+        Intrinsic::Add | Intrinsic::Mult | Intrinsic::Eq | Intrinsic::NotEq | Intrinsic::BitwiseAnd
+            | Intrinsic::BitwiseOr | Intrinsic::LogicalAnd | Intrinsic::LogicalOr
+    )
+}
+
+/// Puts `instr` in a canonical form, so that instrs computing the same value compare equal
+fn canonicalize(mut instr: Instr) -> Instr {
+    if let Instr::Intrinsic { intr, arguments, .. } = &mut instr {
+        if is_commutative(*intr) {
+            arguments.sort();
+        }
+    }
+    instr
+}
+
+impl Code {
+    /// Replaces each pure instr in `func` with an equal instr that dominates it, if there is one.
+    /// Instrs in unreachable blocks are left alone. Returns the number of instrs replaced.
+    pub fn gvn(&mut self, func: FuncId) -> usize {
+        let function = &self.mir_code.functions[func];
+        if function.blocks.is_empty() {
+            return 0;
+        }
+        let cfg = Cfg::new(self, function);
+        let dom = DomTree::new(self, function, &cfg);
+
+        // Visiting blocks in dominator tree preorder means every candidate for replacing an instr
+        // has been seen by the time the instr is. Candidates are only kept if they weren't
+        // themselves replaced.
+        let mut values: HashMap<Instr, Vec<OpId>> = HashMap::new();
+        let mut num_replaced = 0;
+        for block in dom.preorder() {
+            let mut i = 0;
+            while i < self.blocks[block].ops.len() {
+                let op = self.blocks[block].ops[i];
+                let instr = match self.ops[op].as_mir_instr() {
+                    Some(instr) if instr.is_pure() => canonicalize(instr.clone()),
+                    _ => {
+                        i += 1;
+                        continue;
+                    },
+                };
+                let candidates = values.entry(instr).or_default();
+                match candidates.iter().copied().find(|&candidate| dom.op_dominates(candidate, op)) {
+                    Some(existing) => {
+                        self.replace_all_uses(op, existing);
+                        self.erase_op(block, op).unwrap();
+                        num_replaced += 1;
+                    },
+                    None => {
+                        candidates.push(op);
+                        i += 1;
+                    },
+                }
+            }
+        }
+        num_replaced
+    }
+}
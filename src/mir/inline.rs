@@ -0,0 +1,205 @@
+//! Inlining of calls to small functions, or to functions marked with an inline attribute.
+
+use std::collections::HashMap;
+
+use smallvec::SmallVec;
+use string_interner::DefaultSymbol as Sym;
+
+use crate::mir::{FuncId, Instr, PhiEdge, VOID_INSTR};
+use crate::{Code, Block, OpId};
+
+#[derive(Debug, Clone)]
+pub struct InlineOptions {
+    /// Callees with at most this many instrs, not counting `Void`s and `Parameter`s, are inlined
+    pub max_size: usize,
+    /// Callees whose decl has this attribute are inlined regardless of size
+    pub inline_attr: Option<Sym>,
+}
+
+impl Default for InlineOptions {
+    fn default() -> Self {
+        InlineOptions {
+            max_size: 16,
+            inline_attr: None,
+        }
+    }
+}
+
+impl Code {
+    /// The number of instrs in `func` that inlining it would copy
+    pub fn inline_cost(&self, func: FuncId) -> usize {
+        self.mir_code.functions[func].blocks.iter()
+            .flat_map(|&block| self.blocks[block].ops.iter())
+            .filter(|&&op| !matches!(self.ops[op].as_mir_instr(), Some(Instr::Void) | Some(Instr::Parameter(_))))
+            .count()
+    }
+
+    fn has_attribute(&self, func: FuncId, attr: Sym) -> bool {
+        self.mir_code.functions[func].decl
+            .and_then(|decl| self.hir_code.decl_attributes.get(&decl))
+            .map(|attributes| attributes.iter().any(|attribute| attribute.attr == attr))
+            .unwrap_or(false)
+    }
+
+    /// Whether a call from `caller` to `callee` can and should be inlined
+    fn should_inline(&self, caller: FuncId, callee: FuncId, options: &InlineOptions) -> bool {
+        let function = &self.mir_code.functions[callee];
+        // Generic callees would need their types substituted, which isn't supported yet
+        if callee == caller || function.blocks.is_empty() || !function.generic_params.is_empty() {
+            return false;
+        }
+        if self.mir_code.first_unended_block(function).is_some() {
+            return false;
+        }
+        let entry_has_phis = self.blocks[function.blocks[0]].ops.iter()
+            .any(|&op| matches!(self.ops[op].as_mir_instr(), Some(Instr::Phi { .. })));
+        if entry_has_phis {
+            return false;
+        }
+        match options.inline_attr {
+            Some(attr) if self.has_attribute(callee, attr) => true,
+            _ => self.inline_cost(callee) <= options.max_size,
+        }
+    }
+
+    /// Replaces `call` with a copy of the callee's body. Returns `None`, having changed nothing, if
+    /// `call` isn't a call in one of the blocks of `func`, or its arguments don't match the callee's
+    /// parameters.
+    fn inline_call(&mut self, func: FuncId, call: OpId) -> Option<()> {
+        let (callee, arguments) = match self.ops[call].as_mir_instr()? {
+            Instr::Call { func, arguments, .. } => (*func, arguments.clone()),
+            _ => return None,
+        };
+        let callee_blocks = self.mir_code.functions[callee].blocks.clone();
+        let callee_ops = || callee_blocks.iter().flat_map(|&block| self.blocks[block].ops.iter().copied());
+        if callee_blocks.is_empty() || callee_ops().any(|op| self.ops[op].as_mir_instr().is_none()) {
+            return None;
+        }
+        let num_parameters = callee_ops()
+            .filter(|&op| matches!(self.ops[op].as_mir_instr(), Some(Instr::Parameter(_))))
+            .count();
+        if num_parameters != arguments.len() {
+            return None;
+        }
+        let (position, block, index) = self.mir_code.functions[func].blocks.iter()
+            .enumerate()
+            .find_map(|(position, &block)| {
+                let index = self.blocks[block].ops.iter().position(|&op| op == call)?;
+                Some((position, block, index))
+            })?;
+        let continuation = self.split_block(func, block, index + 1);
+
+        // Create a block for each callee block, just before the continuation
+        let mut block_map = HashMap::new();
+        for (i, &callee_block) in callee_blocks.iter().enumerate() {
+            let new_block = self.blocks.push(Block::default());
+            self.mir_code.functions[func].blocks.insert(position + 1 + i, new_block);
+            block_map.insert(callee_block, new_block);
+        }
+
+        // Parameters become the call's arguments and voids become `VOID_INSTR`; everything else is
+        // copied. Ids for the copies are allocated up front, since phis can refer to later ops.
+        let mut op_map: HashMap<OpId, OpId> = HashMap::new();
+        let mut next_argument = 0;
+        for &callee_block in &callee_blocks {
+            for i in 0..self.blocks[callee_block].ops.len() {
+                let op = self.blocks[callee_block].ops[i];
+                let new_op = match self.ops[op].as_mir_instr() {
+                    Some(Instr::Parameter(_)) => {
+                        next_argument += 1;
+                        arguments[next_argument - 1]
+                    },
+                    Some(Instr::Void) => VOID_INSTR,
+                    _ => self.push_mir_instr(Instr::Void),
+                };
+                op_map.insert(op, new_op);
+            }
+        }
+
+        let mut returns: SmallVec<[PhiEdge; 2]> = SmallVec::new();
+        for &callee_block in &callee_blocks {
+            let new_block = block_map[&callee_block];
+            for i in 0..self.blocks[callee_block].ops.len() {
+                let op = self.blocks[callee_block].ops[i];
+                let mut instr = self.ops[op].as_mir_instr()?.clone();
+                if matches!(instr, Instr::Parameter(_) | Instr::Void) {
+                    continue;
+                }
+                for operand in instr.operands_mut() {
+                    *operand = op_map.get(operand).copied().unwrap_or(*operand);
+                }
+                for successor in instr.successors_mut() {
+                    *successor = block_map[successor];
+                }
+                if let Instr::Phi { incoming } = &mut instr {
+                    for edge in incoming {
+                        edge.bb = block_map.get(&edge.bb).copied().unwrap_or(edge.bb);
+                    }
+                }
+                if let Instr::Ret(value) = instr {
+                    returns.push(PhiEdge { bb: new_block, value });
+                    instr = Instr::Br(continuation);
+                }
+
+                let new_op = op_map[&op];
+                self.set_mir_instr(new_op, instr);
+                self.blocks[new_block].ops.push(new_op);
+                if let Some(&range) = self.mir_code.source_ranges.get(&op) {
+                    self.mir_code.source_ranges.insert(new_op, range);
+                }
+                if let Some(name) = self.mir_code.instr_names.get(&op).cloned() {
+                    let name = self.mir_code.functions[func].instr_namespace.insert(name);
+                    self.mir_code.instr_names.insert(new_op, name);
+                }
+            }
+            self.mir_code.start_block(new_block).ok()?;
+            self.mir_code.end_block(new_block).ok()?;
+        }
+
+        // The call's value is the returned value, merged with a phi if the callee returns in more
+        // than one place
+        let result = match returns.len() {
+            0 => VOID_INSTR,
+            1 => returns[0].value,
+            _ => {
+                let phi = self.push_mir_instr(Instr::Phi { incoming: returns });
+                self.blocks[continuation].ops.insert(0, phi);
+                if let Some(&range) = self.mir_code.source_ranges.get(&call) {
+                    self.mir_code.source_ranges.insert(phi, range);
+                }
+                if let Some(name) = self.mir_code.instr_names.get(&call).cloned() {
+                    self.mir_code.instr_names.insert(phi, name);
+                }
+                phi
+            },
+        };
+        self.replace_all_uses(call, result);
+        self.erase_op(block, call).ok()?;
+        let br = *self.blocks[block].ops.last()?;
+        self.set_mir_instr(br, Instr::Br(block_map[&callee_blocks[0]]));
+        Some(())
+    }
+
+    /// Inlines the calls in `func` chosen by `options`, returning the number of calls inlined.
+    /// Calls that only appear once a callee has been inlined are left alone.
+    pub fn inline_calls(&mut self, func: FuncId, options: &InlineOptions) -> usize {
+        let mut calls = Vec::new();
+        for &block in &self.mir_code.functions[func].blocks {
+            for &op in &self.blocks[block].ops {
+                if let Some(&Instr::Call { func: callee, .. }) = self.ops[op].as_mir_instr() {
+                    if self.should_inline(func, callee, options) {
+                        calls.push(op);
+                    }
+                }
+            }
+        }
+
+        let mut num_inlined = 0;
+        for call in calls {
+            if self.inline_call(func, call).is_some() {
+                num_inlined += 1;
+            }
+        }
+        num_inlined
+    }
+}
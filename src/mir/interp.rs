@@ -0,0 +1,684 @@
+//! An interpreter for MIR, used to evaluate functions at compile time.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use smallvec::SmallVec;
+
+use crate::arch::Arch;
+use crate::hir::{Intrinsic, StructId, EnumId, GenericParamId};
+use crate::mir::{Const, Instr, FuncId, StaticId, StrId, VOID_INSTR};
+use crate::mir::eval::{self, CastKind, EvalError};
+use crate::mir::layout::LayoutError;
+use crate::ty::Type;
+use crate::{Code, BlockId, OpId};
+
+/// A location in interpreter memory. `path` is a sequence of field and element indices into the
+/// allocation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pointer {
+    pub alloc: usize,
+    pub path: SmallVec<[usize; 2]>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Void,
+    Uninit,
+    Const(Const),
+    Ptr(Pointer),
+    Struct { id: StructId, fields: Vec<Value> },
+    /// `ty` is the type of the whole array
+    Array { elems: Vec<Value>, ty: Type },
+    /// `ptr` points to the first element
+    Slice { ptr: Pointer, len: usize },
+    Variant { enuum: EnumId, index: usize, payload: Box<Value> },
+}
+
+impl Value {
+    pub fn from_const(konst: Const) -> Value {
+        match konst {
+            Const::StructLit { fields, id } => Value::Struct {
+                id,
+                fields: fields.into_iter().map(Value::from_const).collect(),
+            },
+            Const::Array { elems, ty } => Value::Array {
+                elems: elems.into_iter().map(Value::from_const).collect(),
+                ty,
+            },
+            konst => Value::Const(konst),
+        }
+    }
+
+    /// Converts the value to a constant, if it doesn't depend on interpreter memory
+    pub fn to_const(&self) -> Option<Const> {
+        match self {
+            Value::Const(konst) => Some(konst.clone()),
+            &Value::Struct { id, ref fields } => {
+                let fields = fields.iter().map(|field| field.to_const()).collect::<Option<Vec<_>>>()?;
+                Some(Const::StructLit { fields, id })
+            },
+            &Value::Array { ref elems, ty } => {
+                let elems = elems.iter().map(|elem| elem.to_const()).collect::<Option<Vec<_>>>()?;
+                Some(Const::Array { elems, ty })
+            },
+            &Value::Variant { enuum, index, ref payload } if **payload == Value::Void => Some(Const::BasicVariant { enuum, index }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InterpErrorKind {
+    /// A block contains an op that is not a MIR instr
+    NotMir,
+    /// An operand was read before the interpreter computed its value
+    UndefinedValue(OpId),
+    /// An operand has the wrong kind of value for the instr
+    InvalidOperands,
+    Eval(EvalError),
+    UninitializedRead,
+    InvalidPointer,
+    UseAfterFree,
+    Panic(Option<String>),
+    ArgumentCount { expected: usize, found: usize },
+    GenericArgumentCount { expected: usize, found: usize },
+    /// A block ended without a terminator
+    FellOffBlock(BlockId),
+    /// A phi has no incoming value for the block that control arrived from
+    MissingPhiIncoming(BlockId),
+    /// The size or alignment of a type is unknown
+    UnknownLayout(Type),
+    /// Calls were nested more deeply than `Interpreter::set_max_call_depth` allows
+    CallDepthExceeded(usize),
+    /// Writing the output of `Print` or `PrintType` failed
+    Output(io::Error),
+    /// An array or slice was indexed past its end
+    IndexOutOfBounds { index: u64, len: usize },
+    /// The result of `Interpreter::eval` could not be represented as a `Const`
+    NotConst,
+    /// An error occurred in a function called by this instr
+    InCallee(Box<InterpError>),
+}
+
+#[derive(Debug)]
+pub struct InterpError {
+    pub kind: InterpErrorKind,
+    pub func: FuncId,
+    pub op: Option<OpId>,
+}
+
+struct Frame {
+    values: HashMap<OpId, Value>,
+    generic_arguments: HashMap<GenericParamId, Type>,
+    arguments: Vec<Value>,
+    next_parameter: usize,
+}
+
+/// The default limit on nested calls, low enough that the interpreter's own recursion fits in the
+/// stack of a spawned thread
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 128;
+
+pub struct Interpreter<'a> {
+    code: &'a Code,
+    arch: Arch,
+    /// Allocations made by `Alloca`, `Malloc`, statics and strings. Freed allocations are `None`.
+    memory: Vec<Option<Value>>,
+    statics: HashMap<StaticId, usize>,
+    /// The allocation holding the bytes of each string that has been read through, including the
+    /// nul terminator
+    strings: HashMap<StrId, usize>,
+    call_depth: usize,
+    max_call_depth: usize,
+    /// Where `Print` and `PrintType` write to
+    output: Box<dyn Write + 'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(code: &'a Code, arch: Arch) -> Self {
+        Interpreter {
+            code,
+            arch,
+            memory: Vec::new(),
+            statics: HashMap::new(),
+            strings: HashMap::new(),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            output: Box::new(io::stdout()),
+        }
+    }
+
+    /// Sends the output of `Print` and `PrintType` to `output` instead of stdout
+    pub fn set_output(&mut self, output: Box<dyn Write + 'a>) {
+        self.output = output;
+    }
+
+    /// Limits how deeply calls can nest, so that runaway recursion is an error instead of
+    /// overflowing the stack
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Calls `func` and converts its result to a `Const`
+    pub fn eval(&mut self, func: FuncId, arguments: Vec<Const>, generic_arguments: Vec<Type>) -> Result<Const, InterpError> {
+        let arguments = arguments.into_iter().map(Value::from_const).collect();
+        let result = self.call(func, arguments, generic_arguments)?;
+        result.to_const().ok_or(InterpError { kind: InterpErrorKind::NotConst, func, op: None })
+    }
+
+    pub fn call(&mut self, func: FuncId, arguments: Vec<Value>, generic_arguments: Vec<Type>) -> Result<Value, InterpError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(InterpError { kind: InterpErrorKind::CallDepthExceeded(self.max_call_depth), func, op: None });
+        }
+        self.call_depth += 1;
+        let result = self.call_in_frame(func, arguments, generic_arguments);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_in_frame(&mut self, func: FuncId, arguments: Vec<Value>, generic_arguments: Vec<Type>) -> Result<Value, InterpError> {
+        let code = self.code;
+        let function = &code.mir_code.functions[func];
+        let error = |kind: InterpErrorKind, op: Option<OpId>| InterpError { kind, func, op };
+        if generic_arguments.len() != function.generic_params.len() {
+            let kind = InterpErrorKind::GenericArgumentCount { expected: function.generic_params.len(), found: generic_arguments.len() };
+            return Err(error(kind, None));
+        }
+        let num_parameters = code.num_parameters(function);
+        if arguments.len() != num_parameters {
+            return Err(error(InterpErrorKind::ArgumentCount { expected: num_parameters, found: arguments.len() }, None));
+        }
+        let mut frame = Frame {
+            values: HashMap::new(),
+            generic_arguments: function.generic_params.iter().copied().zip(generic_arguments).collect(),
+            arguments,
+            next_parameter: 0,
+        };
+
+        let mut block = function.blocks[0];
+        let mut previous_block = None;
+        loop {
+            // The leading phis of a block all read their incoming values on entry, before any of
+            // them are defined
+            let mut phi_values = Vec::new();
+            for &op in &code.blocks[block].ops {
+                let incoming = match code.ops[op].as_mir_instr() {
+                    Some(Instr::Phi { incoming }) => incoming,
+                    _ => break,
+                };
+                let edge = incoming.iter()
+                    .find(|edge| Some(edge.bb) == previous_block)
+                    .ok_or_else(|| error(InterpErrorKind::MissingPhiIncoming(previous_block.unwrap_or(block)), Some(op)))?;
+                let value = self.get(&frame, edge.value).map_err(|kind| error(kind, Some(op)))?;
+                phi_values.push((op, value));
+            }
+            frame.values.extend(phi_values);
+
+            let mut next_block = None;
+            for &op in &code.blocks[block].ops {
+                let instr = code.ops[op].as_mir_instr().ok_or_else(|| error(InterpErrorKind::NotMir, Some(op)))?;
+                let wrap = |kind| error(kind, Some(op));
+                match instr {
+                    &Instr::Ret(val) => return self.get(&frame, val).map_err(wrap),
+                    &Instr::Br(bb) => next_block = Some(bb),
+                    &Instr::CondBr { condition, true_bb, false_bb } => match self.get(&frame, condition).map_err(wrap)? {
+                        Value::Const(Const::Bool(condition)) => next_block = Some(if condition { true_bb } else { false_bb }),
+                        _ => return Err(wrap(InterpErrorKind::InvalidOperands)),
+                    },
+                    Instr::SwitchBr { scrutinee, cases, catch_all_bb } => {
+                        let scrutinee = self.get(&frame, *scrutinee).map_err(wrap)?;
+                        let case = cases.iter().find(|case| switch_case_matches(&scrutinee, &case.value));
+                        next_block = Some(case.map(|case| case.bb).unwrap_or(*catch_all_bb));
+                    },
+                    Instr::Phi { .. } => if !frame.values.contains_key(&op) {
+                        // Not in the leading run of phis
+                        return Err(wrap(InterpErrorKind::UndefinedValue(op)));
+                    },
+                    _ => {
+                        let value = self.execute(&mut frame, instr).map_err(wrap)?;
+                        frame.values.insert(op, value);
+                    },
+                }
+                if next_block.is_some() {
+                    break;
+                }
+            }
+            previous_block = Some(block);
+            block = next_block.ok_or_else(|| error(InterpErrorKind::FellOffBlock(block), None))?;
+        }
+    }
+
+    fn get(&self, frame: &Frame, op: OpId) -> Result<Value, InterpErrorKind> {
+        if op == VOID_INSTR {
+            return Ok(Value::Void);
+        }
+        frame.values.get(&op).cloned().ok_or(InterpErrorKind::UndefinedValue(op))
+    }
+
+    fn get_const(&self, frame: &Frame, op: OpId) -> Result<Const, InterpErrorKind> {
+        match self.get(frame, op)? {
+            Value::Const(konst) => Ok(konst),
+            _ => Err(InterpErrorKind::InvalidOperands),
+        }
+    }
+
+    fn get_ptr(&self, frame: &Frame, op: OpId) -> Result<Pointer, InterpErrorKind> {
+        match self.get(frame, op)? {
+            Value::Ptr(ptr) => Ok(ptr),
+            _ => Err(InterpErrorKind::InvalidPointer),
+        }
+    }
+
+    fn get_ty(&self, frame: &Frame, op: OpId) -> Result<Type, InterpErrorKind> {
+        match self.get_const(frame, op)? {
+            Const::Ty(ty) => Ok(ty),
+            _ => Err(InterpErrorKind::InvalidOperands),
+        }
+    }
+
+    /// Whether `value` could be of type `ty`. Pointers and uninitialized values don't record
+    /// what they point to or what they will hold, so they are only checked as far as they can be.
+    fn may_have_type(&self, value: &Value, ty: Type) -> bool {
+        match value {
+            Value::Void => ty == Type::Void,
+            Value::Uninit => true,
+            Value::Const(konst) => konst.ty() == ty,
+            Value::Ptr(_) => matches!(ty, Type::Pointer { .. }),
+            &Value::Struct { id, .. } => ty == Type::Struct(id),
+            Value::Array { ty: array_ty, .. } => *array_ty == ty,
+            Value::Slice { .. } => matches!(ty, Type::Slice { .. }),
+            &Value::Variant { enuum, .. } => ty == Type::Enum(enuum),
+        }
+    }
+
+    /// Replaces generic parameters in `ty` with the current frame's generic arguments
+    fn substitute(&self, frame: &Frame, ty: &Type) -> Type {
+        match ty {
+            Type::GenericParam(id) => frame.generic_arguments.get(id).copied().unwrap_or(*ty),
+            &Type::Pointer { pointee, is_mut } => {
+                let pointee = self.substitute(frame, &self.code.types.get(pointee));
+                self.code.types.ptr_with_mut(pointee, is_mut)
+            },
+            &Type::Array { elem, len } => self.code.types.array(self.substitute(frame, &self.code.types.get(elem)), len),
+            &Type::Slice { elem, is_mut } => self.code.types.slice(self.substitute(frame, &self.code.types.get(elem)), is_mut),
+            &Type::Function { params, ret } => {
+                let params: Vec<Type> = self.code.types.get_list(params).iter().map(|param| self.substitute(frame, param)).collect();
+                self.code.types.function(&params, self.substitute(frame, &self.code.types.get(ret)))
+            },
+            _ => *ty,
+        }
+    }
+
+    fn substitute_const(&self, frame: &Frame, konst: &Const) -> Const {
+        match konst {
+            &Const::Int { lit, ref ty } => Const::Int { lit, ty: self.substitute(frame, ty) },
+            &Const::Float { lit, ref ty } => Const::Float { lit, ty: self.substitute(frame, ty) },
+            &Const::Str { id, ref ty } => Const::Str { id, ty: self.substitute(frame, ty) },
+            &Const::Func { id, ref ty } => Const::Func { id, ty: self.substitute(frame, ty) },
+            Const::Ty(ty) => Const::Ty(self.substitute(frame, ty)),
+            &Const::StructLit { ref fields, id } => Const::StructLit {
+                fields: fields.iter().map(|field| self.substitute_const(frame, field)).collect(),
+                id,
+            },
+            Const::Array { elems, ty } => Const::Array {
+                elems: elems.iter().map(|elem| self.substitute_const(frame, elem)).collect(),
+                ty: self.substitute(frame, ty),
+            },
+            _ => konst.clone(),
+        }
+    }
+
+    /// Returns an uninitialized value of type `ty`, with struct fields and array elements laid out
+    /// so they can be written individually
+    fn uninit(&self, ty: &Type) -> Value {
+        match ty {
+            &Type::Struct(id) => match self.code.mir_code.structs.get(&id) {
+                Some(strukt) => Value::Struct {
+                    id,
+                    fields: strukt.field_tys.iter().map(|ty| self.uninit(ty)).collect(),
+                },
+                None => Value::Uninit,
+            },
+            Type::Array { elem, len } => Value::Array {
+                elems: vec![self.uninit(&self.code.types.get(*elem)); *len],
+                ty: *ty,
+            },
+            _ => Value::Uninit,
+        }
+    }
+
+    fn allocate(&mut self, value: Value) -> Pointer {
+        self.memory.push(Some(value));
+        Pointer { alloc: self.memory.len() - 1, path: SmallVec::new() }
+    }
+
+    fn place(&mut self, ptr: &Pointer) -> Result<&mut Value, InterpErrorKind> {
+        let mut place = self.memory.get_mut(ptr.alloc)
+            .ok_or(InterpErrorKind::InvalidPointer)?
+            .as_mut()
+            .ok_or(InterpErrorKind::UseAfterFree)?;
+        for &index in &ptr.path {
+            place = match place {
+                Value::Struct { fields, .. } | Value::Array { elems: fields, .. } => fields.get_mut(index).ok_or(InterpErrorKind::InvalidPointer)?,
+                _ => return Err(InterpErrorKind::InvalidPointer),
+            };
+        }
+        Ok(place)
+    }
+
+    /// Returns a pointer to the first byte of the string `id`, copying the string into memory as
+    /// an array of `u8`s the first time
+    fn str_ptr(&mut self, id: StrId) -> Pointer {
+        let alloc = match self.strings.get(&id) {
+            Some(&alloc) => alloc,
+            None => {
+                let bytes = self.code.mir_code.strings[id].as_bytes_with_nul();
+                let value = Value::Array {
+                    elems: bytes.iter().map(|&byte| Value::Const(Const::Int { lit: byte as u64, ty: Type::u8() })).collect(),
+                    ty: self.code.types.array(Type::u8(), bytes.len()),
+                };
+                let alloc = self.allocate(value).alloc;
+                self.strings.insert(id, alloc);
+                alloc
+            },
+        };
+        Pointer { alloc, path: SmallVec::from_slice(&[0]) }
+    }
+
+    /// Converts a value used as a pointer to a `Pointer`. Strings point to their first byte.
+    fn as_ptr(&mut self, value: Value) -> Result<Pointer, InterpErrorKind> {
+        match value {
+            Value::Ptr(ptr) => Ok(ptr),
+            Value::Const(Const::Str { id, .. }) => Ok(self.str_ptr(id)),
+            _ => Err(InterpErrorKind::InvalidPointer),
+        }
+    }
+
+    fn load(&mut self, location: Value) -> Result<Value, InterpErrorKind> {
+        let ptr = self.as_ptr(location)?;
+        let value = self.place(&ptr)?.clone();
+        if value == Value::Uninit {
+            return Err(InterpErrorKind::UninitializedRead);
+        }
+        Ok(value)
+    }
+
+    fn get_index(&self, frame: &Frame, op: OpId) -> Result<u64, InterpErrorKind> {
+        match self.get_const(frame, op)? {
+            Const::Int { lit, .. } => Ok(lit),
+            _ => Err(InterpErrorKind::InvalidOperands),
+        }
+    }
+
+    /// Returns the pointer to the element `index` of the array `ptr` points to, or of the slice
+    /// `ptr`
+    fn element_address(&mut self, ptr: Value, index: u64) -> Result<Pointer, InterpErrorKind> {
+        let (mut ptr, len) = match ptr {
+            Value::Ptr(mut ptr) => {
+                let len = match self.place(&ptr)? {
+                    Value::Array { elems, .. } => elems.len(),
+                    _ => return Err(InterpErrorKind::InvalidOperands),
+                };
+                ptr.path.push(0);
+                (ptr, len)
+            },
+            Value::Slice { ptr, len } => (ptr, len),
+            _ => return Err(InterpErrorKind::InvalidOperands),
+        };
+        if index >= len as u64 {
+            return Err(InterpErrorKind::IndexOutOfBounds { index, len });
+        }
+        // Slices can start partway into an array, so the index is relative to the first element
+        *ptr.path.last_mut().ok_or(InterpErrorKind::InvalidPointer)? += index as usize;
+        Ok(ptr)
+    }
+
+    fn static_ptr(&mut self, id: StaticId) -> Pointer {
+        let alloc = match self.statics.get(&id) {
+            Some(&alloc) => alloc,
+            None => {
+                let value = Value::from_const(self.code.mir_code.statics[id].val.clone());
+                let alloc = self.allocate(value).alloc;
+                self.statics.insert(id, alloc);
+                alloc
+            },
+        };
+        Pointer { alloc, path: SmallVec::new() }
+    }
+
+    fn print(&mut self, value: &Value) -> Result<(), InterpErrorKind> {
+        let code = self.code;
+        match value {
+            &Value::Const(Const::Str { id, .. }) => write!(self.output, "{}", code.mir_code.strings[id].to_string_lossy()),
+            Value::Const(Const::Int { lit, ty }) => match eval::int_info(ty, self.arch) {
+                Some((bits, true)) => write!(self.output, "{}", eval::sign_extend(*lit, bits)),
+                _ => write!(self.output, "{}", lit),
+            },
+            Value::Const(Const::Float { lit, .. }) => write!(self.output, "{}", lit),
+            Value::Const(Const::Bool(val)) => write!(self.output, "{}", val),
+            Value::Const(Const::Ty(ty)) => write!(self.output, "{}", code.display_ty(ty)),
+            _ => return Err(InterpErrorKind::InvalidOperands),
+        }.map_err(InterpErrorKind::Output)
+    }
+
+    fn execute_intrinsic(&mut self, frame: &Frame, arguments: &[OpId], ty: &Type, intr: Intrinsic) -> Result<Value, InterpErrorKind> {
+        let arguments = arguments.iter()
+            .map(|&arg| self.get(frame, arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = match intr {
+            Intrinsic::Print => {
+                for argument in &arguments {
+                    self.print(argument)?;
+                }
+                Value::Void
+            },
+            Intrinsic::PrintType => {
+                for argument in &arguments {
+                    match argument {
+                        Value::Const(Const::Ty(ty)) => write!(self.output, "{}", self.code.display_ty(ty)).map_err(InterpErrorKind::Output)?,
+                        _ => return Err(InterpErrorKind::InvalidOperands),
+                    }
+                }
+                Value::Void
+            },
+            Intrinsic::Panic => {
+                let message = match arguments.first() {
+                    Some(&Value::Const(Const::Str { id, .. })) => Some(self.code.mir_code.strings[id].to_string_lossy().into_owned()),
+                    _ => None,
+                };
+                return Err(InterpErrorKind::Panic(message));
+            },
+            Intrinsic::Malloc => Value::Ptr(self.allocate(Value::Uninit)),
+            Intrinsic::Free => match arguments.first() {
+                Some(Value::Ptr(ptr)) if ptr.path.is_empty() => match self.memory.get_mut(ptr.alloc) {
+                    Some(alloc @ Some(_)) => {
+                        *alloc = None;
+                        Value::Void
+                    },
+                    Some(None) => return Err(InterpErrorKind::UseAfterFree),
+                    None => return Err(InterpErrorKind::InvalidPointer),
+                },
+                _ => return Err(InterpErrorKind::InvalidPointer),
+            },
+            Intrinsic::SizeOf | Intrinsic::StrideOf | Intrinsic::AlignOf | Intrinsic::OffsetOf => {
+                let arguments = arguments.iter()
+                    .map(|argument| match argument {
+                        Value::Const(Const::Ty(ty)) => Ok(Const::Ty(self.substitute(frame, ty))),
+                        Value::Const(konst) => Ok(konst.clone()),
+                        _ => Err(InterpErrorKind::InvalidOperands),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let konst = self.code.eval_layout_intrinsic(intr, &arguments, &self.substitute(frame, ty), self.arch)
+                    .map_err(|error| match error {
+                        LayoutError::UnknownLayout(ty) => InterpErrorKind::UnknownLayout(ty),
+                        LayoutError::InvalidOperands | LayoutError::NotSerializable(_) => InterpErrorKind::InvalidOperands,
+                    })?;
+                Value::Const(konst)
+            },
+            Intrinsic::Eq | Intrinsic::NotEq if matches!(arguments.as_slice(), [Value::Ptr(_), Value::Ptr(_)]) => {
+                let equal = arguments[0] == arguments[1];
+                Value::Const(Const::Bool(equal == (intr == Intrinsic::Eq)))
+            },
+            _ => {
+                let arguments = arguments.iter()
+                    .map(|argument| match argument {
+                        Value::Const(konst) => Ok(konst.clone()),
+                        _ => Err(InterpErrorKind::InvalidOperands),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::Const(eval::eval_intrinsic(intr, &arguments, self.arch).map_err(InterpErrorKind::Eval)?)
+            },
+        };
+        Ok(value)
+    }
+
+    fn execute(&mut self, frame: &mut Frame, instr: &Instr) -> Result<Value, InterpErrorKind> {
+        if let Some((kind, op, ty)) = CastKind::of(instr) {
+            let ty = self.substitute(frame, ty);
+            return match self.get(frame, op)? {
+                Value::Ptr(ptr) if kind == CastKind::Reinterpret && matches!(ty, Type::Pointer { .. }) => Ok(Value::Ptr(ptr)),
+                Value::Const(konst) => eval::eval_cast(kind, &konst, &ty, self.arch)
+                    .map(Value::Const)
+                    .map_err(InterpErrorKind::Eval),
+                _ => Err(InterpErrorKind::InvalidOperands),
+            };
+        }
+        let value = match instr {
+            Instr::Void => Value::Void,
+            Instr::Const(konst) => Value::from_const(self.substitute_const(frame, konst)),
+            Instr::Alloca(ty) => {
+                let value = self.uninit(&self.substitute(frame, ty));
+                Value::Ptr(self.allocate(value))
+            },
+            &Instr::LogicalNot(op) => {
+                let val = self.get_const(frame, op)?;
+                Value::Const(eval::eval_intrinsic(Intrinsic::LogicalNot, &[val], self.arch).map_err(InterpErrorKind::Eval)?)
+            },
+            Instr::Call { arguments, generic_arguments, func } => {
+                let arguments = arguments.iter()
+                    .map(|&arg| self.get(frame, arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let generic_arguments = generic_arguments.iter()
+                    .map(|ty| self.substitute(frame, ty))
+                    .collect();
+                self.call(*func, arguments, generic_arguments)
+                    .map_err(|error| InterpErrorKind::InCallee(Box::new(error)))?
+            },
+            &Instr::CallIndirect { ref arguments, callee } => {
+                let (func, ty) = match self.get_const(frame, callee)? {
+                    Const::Func { id, ty } => (id, ty),
+                    _ => return Err(InterpErrorKind::InvalidOperands),
+                };
+                // A function pointer has no generic arguments to pass, and must have the type of the
+                // function it points to
+                let code = self.code;
+                let function = &code.mir_code.functions[func];
+                if !function.generic_params.is_empty() || ty != code.func_ptr_ty(function) {
+                    return Err(InterpErrorKind::InvalidOperands);
+                }
+                let (params, _) = code.types.signature(ty).ok_or(InterpErrorKind::InvalidOperands)?;
+                let arguments = arguments.iter()
+                    .map(|&arg| self.get(frame, arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let arguments_match = arguments.len() == params.len()
+                    && arguments.iter().zip(params).all(|(argument, &param)| self.may_have_type(argument, param));
+                if !arguments_match {
+                    return Err(InterpErrorKind::InvalidOperands);
+                }
+                self.call(func, arguments, Vec::new())
+                    .map_err(|error| InterpErrorKind::InCallee(Box::new(error)))?
+            },
+            Instr::Intrinsic { arguments, ty, intr } => self.execute_intrinsic(frame, arguments, ty, *intr)?,
+            &Instr::Load(location) => {
+                let location = self.get(frame, location)?;
+                self.load(location)?
+            },
+            &Instr::Store { location, value } => {
+                let location = self.get_ptr(frame, location)?;
+                let value = self.get(frame, value)?;
+                *self.place(&location)? = value;
+                Value::Void
+            },
+            &Instr::AddressOfStatic(id) => Value::Ptr(self.static_ptr(id)),
+            &Instr::Pointer { op, is_mut } => Value::Const(Const::Ty(self.code.types.ptr_with_mut(self.get_ty(frame, op)?, is_mut))),
+            &Instr::Struct { id, .. } => Value::Const(Const::Ty(Type::Struct(id))),
+            &Instr::Enum { id, .. } => Value::Const(Const::Ty(Type::Enum(id))),
+            &Instr::StructLit { ref fields, id } => Value::Struct {
+                id,
+                fields: fields.iter().map(|&field| self.get(frame, field)).collect::<Result<_, _>>()?,
+            },
+            &Instr::DirectFieldAccess { val, index } => match self.get(frame, val)? {
+                Value::Struct { mut fields, .. } if index < fields.len() => fields.swap_remove(index),
+                _ => return Err(InterpErrorKind::InvalidOperands),
+            },
+            &Instr::IndirectFieldAccess { val, index } => {
+                let mut ptr = self.get_ptr(frame, val)?;
+                ptr.path.push(index);
+                Value::Ptr(ptr)
+            },
+            &Instr::ElementAccess { val, index } => {
+                let index = self.get_index(frame, index)?;
+                match self.get(frame, val)? {
+                    Value::Array { mut elems, .. } if index < elems.len() as u64 => elems.swap_remove(index as usize),
+                    Value::Array { elems, .. } => return Err(InterpErrorKind::IndexOutOfBounds { index, len: elems.len() }),
+                    _ => return Err(InterpErrorKind::InvalidOperands),
+                }
+            },
+            &Instr::ElementAddress { ptr, index } => {
+                let index = self.get_index(frame, index)?;
+                let ptr = self.get(frame, ptr)?;
+                Value::Ptr(self.element_address(ptr, index)?)
+            },
+            &Instr::MakeSlice { ptr, len } => {
+                let ptr = self.get(frame, ptr)?;
+                let ptr = self.as_ptr(ptr)?;
+                let len = self.get_index(frame, len)? as usize;
+                // A slice's pointer must point to an element of an array, so that it can be offset by
+                // an index
+                if len > 0 {
+                    let (_, array_path) = ptr.path.split_last().ok_or(InterpErrorKind::InvalidPointer)?;
+                    let array = Pointer { alloc: ptr.alloc, path: SmallVec::from_slice(array_path) };
+                    if !matches!(self.place(&array)?, Value::Array { .. }) {
+                        return Err(InterpErrorKind::InvalidOperands);
+                    }
+                }
+                Value::Slice { ptr, len }
+            },
+            &Instr::SliceLen(op) => match self.get(frame, op)? {
+                Value::Slice { len, .. } => Value::Const(Const::Int { lit: len as u64, ty: Type::usize() }),
+                _ => return Err(InterpErrorKind::InvalidOperands),
+            },
+            &Instr::Variant { enuum, index, payload } => Value::Variant { enuum, index, payload: Box::new(self.get(frame, payload)?) },
+            &Instr::DiscriminantAccess { val } => match self.get(frame, val)? {
+                Value::Variant { enuum, index, .. } => Value::Const(Const::BasicVariant { enuum, index }),
+                Value::Const(konst @ Const::BasicVariant { .. }) => Value::Const(konst),
+                _ => return Err(InterpErrorKind::InvalidOperands),
+            },
+            Instr::GenericParam(id) => match frame.generic_arguments.get(id) {
+                Some(ty) => Value::Const(Const::Ty(*ty)),
+                None => return Err(InterpErrorKind::InvalidOperands),
+            },
+            Instr::Parameter(_) => {
+                let argument = frame.arguments.get(frame.next_parameter).cloned().ok_or(InterpErrorKind::InvalidOperands)?;
+                frame.next_parameter += 1;
+                argument
+            },
+            Instr::Reinterpret(..) | Instr::Truncate(..) | Instr::SignExtend(..) | Instr::ZeroExtend(..)
+                | Instr::FloatCast(..) | Instr::FloatToInt(..) | Instr::IntToFloat(..) => unreachable!("casts are handled above"),
+            Instr::Ret(_) | Instr::Br(_) | Instr::CondBr { .. } | Instr::SwitchBr { .. } | Instr::Phi { .. }
+                => unreachable!("terminators and phis are handled by `call`"),
+        };
+        Ok(value)
+    }
+}
+
+fn switch_case_matches(scrutinee: &Value, case: &Const) -> bool {
+    match (scrutinee, case) {
+        (Value::Const(Const::Int { lit: a, .. }), Const::Int { lit: b, .. }) => a == b,
+        (&Value::Variant { enuum, index, .. }, &Const::BasicVariant { enuum: case_enuum, index: case_index }) => {
+            enuum == case_enuum && index == case_index
+        },
+        (Value::Const(konst), case) => konst == case,
+        _ => false,
+    }
+}
@@ -0,0 +1,439 @@
+//! Sizes, alignments and field offsets of types on a given `Arch`. Structs are laid out like C
+//! structs: fields in declaration order, each at the next offset that satisfies its alignment.
+//! Arrays store their elements one stride apart, and slices are a pointer to the first element
+//! followed by a `usize` length.
+//!
+//! Enums store their discriminant as a tag of the smallest unsigned int type that can hold every
+//! variant index, followed by the payload of the variant. When every variant but one has an empty
+//! payload, and that payload has enough invalid bit patterns, such as a `bool` other than 0 or 1,
+//! or a null pointer on targets where address 0 isn't valid, the other variants are stored as
+//! those bit patterns instead and no tag is needed.
+
+use smallvec::SmallVec;
+
+use crate::arch::{Arch, Endianness};
+use crate::hir::{Intrinsic, StructId, EnumId};
+use crate::mir::eval::{self, EvalError};
+use crate::mir::{Const, Instr, Struct, StructLayout, Enum, EnumLayout, DiscriminantEncoding};
+use crate::ty::{Type, FloatWidth};
+use crate::{Code, OpId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    /// The type has no layout: it is a generic parameter or compile-time-only type, or a struct or
+    /// enum that hasn't been laid out
+    UnknownLayout(Type),
+    /// The operands of a layout intrinsic are of the wrong kind, or name a field that doesn't exist
+    InvalidOperands,
+    /// The constant has no in-memory representation that can be written out by itself, like a
+    /// string, which needs a pointer to its data, or a function's address, which isn't known until
+    /// the code is placed in memory
+    NotSerializable(Const),
+}
+
+/// The size, alignment and stride of a type in bytes. The stride is the distance between
+/// consecutive elements of an array: the size rounded up to the alignment.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub alignment: usize,
+    pub stride: usize,
+}
+
+impl Layout {
+    /// The layout of a scalar that is `bits` wide, with an alignment of `alignment` bytes
+    pub fn scalar(bits: usize, alignment: usize) -> Layout {
+        let size = bits / 8;
+        Layout { size, alignment, stride: align_up(size, alignment) }
+    }
+}
+
+/// Invalid bit patterns of a type, which an enum can use to store its discriminant: `count`
+/// consecutive values starting at `start`, of the unsigned int of type `ty` at `offset`
+#[derive(Copy, Clone, Debug)]
+struct Niche {
+    offset: usize,
+    ty: Type,
+    start: u64,
+    count: u64,
+}
+
+/// The smallest unsigned int type that can hold `num_values` different values
+fn tag_ty(num_values: usize) -> Type {
+    if num_values <= 1 << 8 {
+        Type::u8()
+    } else if num_values <= 1 << 16 {
+        Type::u16()
+    } else {
+        Type::u32()
+    }
+}
+
+impl EnumLayout {
+    /// The value stored at the discriminant's location for the variant `index`, or `None` if
+    /// nothing is stored for that variant
+    pub fn encode_discriminant(&self, index: usize) -> Option<u64> {
+        match self.discriminant {
+            DiscriminantEncoding::None => None,
+            DiscriminantEncoding::Tag { .. } => Some(index as u64),
+            DiscriminantEncoding::Niche { dataful_variant, niche_start, .. } => if index == dataful_variant {
+                None
+            } else if index < dataful_variant {
+                Some(niche_start + index as u64)
+            } else {
+                Some(niche_start + index as u64 - 1)
+            },
+        }
+    }
+
+    /// The index of the variant whose discriminant's location holds `value`
+    pub fn decode_discriminant(&self, value: u64) -> usize {
+        match self.discriminant {
+            DiscriminantEncoding::None => 0,
+            DiscriminantEncoding::Tag { .. } => value as usize,
+            DiscriminantEncoding::Niche { dataful_variant, niche_start, .. } => {
+                let num_niche_variants = self.payload_offsets.len() as u64 - 1;
+                if value < niche_start || value - niche_start >= num_niche_variants {
+                    return dataful_variant;
+                }
+                let index = (value - niche_start) as usize;
+                if index < dataful_variant { index } else { index + 1 }
+            },
+        }
+    }
+}
+
+/// Writes the low bits of `value` to `bytes`, in the order given by `endianness`
+fn write_uint(bytes: &mut [u8], value: u64, endianness: Endianness) {
+    let len = bytes.len();
+    for i in 0..len {
+        let byte = (value >> (8 * i)) as u8;
+        match endianness {
+            Endianness::Little => bytes[i] = byte,
+            Endianness::Big => bytes[len - 1 - i] = byte,
+        }
+    }
+}
+
+/// Rounds `offset` up to a multiple of `alignment`, which must be a power of two
+pub fn align_up(offset: usize, alignment: usize) -> usize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+impl Code {
+    /// Computes the layout of `ty` on `arch`. Structs and enums must already have been laid out,
+    /// in `MirCode::structs` and `MirCode::enums`.
+    pub fn layout(&self, ty: &Type, arch: Arch) -> Result<Layout, LayoutError> {
+        let data_layout = arch.data_layout();
+        match ty {
+            &Type::Int { width, .. } => Ok(Layout::scalar(width.bit_width(arch), data_layout.int_align(width).abi)),
+            &Type::Float(width) => {
+                let bits = match width {
+                    FloatWidth::W32 => 32,
+                    FloatWidth::W64 => 64,
+                };
+                Ok(Layout::scalar(bits, data_layout.float_align(width).abi))
+            },
+            Type::Pointer { .. } | Type::Function { .. } => Ok(Layout::scalar(data_layout.pointer_size, data_layout.pointer_align.abi)),
+            &Type::Array { elem, len } => {
+                let elem = self.layout(&self.types.get(elem), arch)?;
+                let size = elem.stride * len;
+                Ok(Layout { size, alignment: elem.alignment, stride: size })
+            },
+            Type::Slice { .. } => {
+                let size = 2 * data_layout.pointer_size / 8;
+                Ok(Layout { size, alignment: data_layout.pointer_align.abi, stride: size })
+            },
+            Type::Bool => Ok(Layout::scalar(8, data_layout.i8_align.abi)),
+            Type::Void | Type::Never => Ok(Layout { size: 0, alignment: 1, stride: 0 }),
+            Type::Struct(id) => match self.mir_code.structs.get(id) {
+                Some(strukt) => Ok(Layout { size: strukt.layout.size, alignment: strukt.layout.alignment, stride: strukt.layout.stride }),
+                None => Err(LayoutError::UnknownLayout(*ty)),
+            },
+            Type::Enum(id) => match self.mir_code.enums.get(id) {
+                Some(enuum) => Ok(Layout { size: enuum.layout.size, alignment: enuum.layout.alignment, stride: enuum.layout.stride }),
+                None => Err(LayoutError::UnknownLayout(*ty)),
+            },
+            Type::Error | Type::Mod | Type::Ty | Type::GenericParam(_) => Err(LayoutError::UnknownLayout(*ty)),
+        }
+    }
+
+    /// Lays out a struct with fields of types `field_tys`
+    pub fn struct_layout(&self, field_tys: &[Type], arch: Arch) -> Result<StructLayout, LayoutError> {
+        let mut field_offsets = SmallVec::new();
+        let mut offset = 0;
+        let mut alignment = 1;
+        for ty in field_tys {
+            let field = self.layout(ty, arch)?;
+            offset = align_up(offset, field.alignment);
+            field_offsets.push(offset);
+            offset += field.size;
+            alignment = alignment.max(field.alignment);
+        }
+        Ok(StructLayout { field_offsets, alignment, size: offset, stride: align_up(offset, alignment) })
+    }
+
+    /// The invalid bit patterns of `ty` with the most values, if it has any
+    fn niche(&self, ty: &Type, arch: Arch) -> Option<Niche> {
+        match ty {
+            Type::Bool => Some(Niche { offset: 0, ty: Type::u8(), start: 2, count: 254 }),
+            Type::Pointer { .. } | Type::Slice { .. } | Type::Function { .. } if !arch.null_address_is_valid() => {
+                Some(Niche { offset: 0, ty: Type::usize(), start: 0, count: 1 })
+            },
+            &Type::Array { elem, len } if len > 0 => self.niche(&self.types.get(elem), arch),
+            Type::Struct(id) => {
+                let strukt = self.mir_code.structs.get(id)?;
+                strukt.field_tys.iter()
+                    .zip(&strukt.layout.field_offsets)
+                    .filter_map(|(ty, &offset)| {
+                        let niche = self.niche(ty, arch)?;
+                        Some(Niche { offset: offset + niche.offset, ..niche })
+                    })
+                    .max_by_key(|niche| niche.count)
+            },
+            Type::Enum(id) => {
+                let enuum = self.mir_code.enums.get(id)?;
+                let num_variants = enuum.variant_tys.len() as u64;
+                match enuum.layout.discriminant {
+                    DiscriminantEncoding::None => {
+                        let niche = self.niche(enuum.variant_tys.first()?, arch)?;
+                        Some(Niche { offset: enuum.layout.payload_offsets[0] + niche.offset, ..niche })
+                    },
+                    DiscriminantEncoding::Tag { offset, ty } => {
+                        let bits = self.layout(&ty, arch).ok()?.size * 8;
+                        Some(Niche { offset, ty, start: num_variants, count: (1 << bits) - num_variants })
+                    },
+                    DiscriminantEncoding::Niche { offset, ty, dataful_variant, niche_start } => {
+                        let niche = self.niche(&enuum.variant_tys[dataful_variant], arch)?;
+                        let used = num_variants - 1;
+                        if niche.count <= used {
+                            return None;
+                        }
+                        Some(Niche { offset, ty, start: niche_start + used, count: niche.count - used })
+                    },
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Lays out an enum whose variants have payloads of types `variant_tys`
+    pub fn enum_layout(&self, variant_tys: &[Type], arch: Arch) -> Result<EnumLayout, LayoutError> {
+        let payloads = variant_tys.iter()
+            .map(|ty| self.layout(ty, arch))
+            .collect::<Result<Vec<_>, _>>()?;
+        let payload_alignment = payloads.iter().map(|payload| payload.alignment).max().unwrap_or(1);
+
+        if payloads.len() <= 1 {
+            let size = payloads.first().map(|payload| payload.size).unwrap_or(0);
+            return Ok(EnumLayout {
+                payload_offsets: payloads.iter().map(|_| 0).collect(),
+                alignment: payload_alignment,
+                size,
+                stride: align_up(size, payload_alignment),
+                discriminant: DiscriminantEncoding::None,
+            });
+        }
+
+        // Use a niche if only one variant has a non-empty payload, and it has room for the rest
+        let mut dataful = (0..payloads.len()).filter(|&i| payloads[i].size > 0);
+        if let (Some(dataful_variant), None) = (dataful.next(), dataful.next()) {
+            if let Some(niche) = self.niche(&variant_tys[dataful_variant], arch) {
+                if niche.count >= payloads.len() as u64 - 1 {
+                    let size = payloads[dataful_variant].size;
+                    return Ok(EnumLayout {
+                        payload_offsets: payloads.iter().map(|_| 0).collect(),
+                        alignment: payload_alignment,
+                        size,
+                        stride: align_up(size, payload_alignment),
+                        discriminant: DiscriminantEncoding::Niche { offset: niche.offset, ty: niche.ty, dataful_variant, niche_start: niche.start },
+                    });
+                }
+            }
+        }
+
+        let tag_ty = tag_ty(payloads.len());
+        let tag = self.layout(&tag_ty, arch)?;
+        let payload_offsets: SmallVec<[usize; 2]> = payloads.iter()
+            .map(|payload| align_up(tag.size, payload.alignment))
+            .collect();
+        let size = payloads.iter()
+            .zip(&payload_offsets)
+            .map(|(payload, &offset)| offset + payload.size)
+            .max()
+            .unwrap()
+            .max(tag.size);
+        let alignment = payload_alignment.max(tag.alignment);
+        Ok(EnumLayout {
+            payload_offsets,
+            alignment,
+            size,
+            stride: align_up(size, alignment),
+            discriminant: DiscriminantEncoding::Tag { offset: 0, ty: tag_ty },
+        })
+    }
+
+    /// Lays out the enum `id` and adds it to `MirCode::enums`, replacing any previous layout
+    pub fn register_enum(&mut self, id: EnumId, variant_tys: SmallVec<[Type; 2]>, arch: Arch) -> Result<(), LayoutError> {
+        let layout = self.enum_layout(&variant_tys, arch)?;
+        self.mir_code.enums.insert(id, Enum { variant_tys, layout });
+        Ok(())
+    }
+
+    /// Lays out the struct `id` and adds it to `MirCode::structs`, replacing any previous layout
+    pub fn register_struct(&mut self, id: StructId, field_tys: SmallVec<[Type; 2]>, arch: Arch) -> Result<(), LayoutError> {
+        let layout = self.struct_layout(&field_tys, arch)?;
+        self.mir_code.structs.insert(id, Struct { field_tys, layout });
+        Ok(())
+    }
+
+    /// The type produced by the op `op`, if it is a type known without executing anything
+    pub(crate) fn type_operand(&self, op: OpId) -> Option<Type> {
+        match *self.ops[op].as_mir_instr()? {
+            Instr::Const(Const::Ty(ty)) => Some(ty),
+            Instr::Struct { id, .. } => Some(Type::Struct(id)),
+            Instr::Enum { id, .. } => Some(Type::Enum(id)),
+            Instr::Pointer { op, is_mut } => Some(self.types.ptr_with_mut(self.type_operand(op)?, is_mut)),
+            _ => None,
+        }
+    }
+
+    /// Lays out every struct and enum declared by a `Struct` or `Enum` instr whose field or payload
+    /// types are known, and which isn't already in `MirCode::structs` or `MirCode::enums`. Types
+    /// containing other structs and enums are laid out after them. The operands of an `Enum` instr
+    /// are the payload types of its variants, `void` for variants without a payload. Returns the
+    /// number of types laid out.
+    pub fn compute_layouts(&mut self, arch: Arch) -> usize {
+        let mut num_registered = 0;
+        loop {
+            let mut pending_structs = Vec::new();
+            let mut pending_enums = Vec::new();
+            for instr in self.ops.iter().filter_map(|op| op.as_mir_instr()) {
+                match instr {
+                    Instr::Struct { fields, id } if !self.mir_code.structs.contains_key(id) => {
+                        let field_tys = fields.iter().map(|&field| self.type_operand(field)).collect::<Option<SmallVec<_>>>();
+                        if let Some(field_tys) = field_tys {
+                            pending_structs.push((*id, field_tys));
+                        }
+                    },
+                    Instr::Enum { variants, id } if !self.mir_code.enums.contains_key(id) => {
+                        let variant_tys = variants.iter().map(|&variant| self.type_operand(variant)).collect::<Option<SmallVec<_>>>();
+                        if let Some(variant_tys) = variant_tys {
+                            pending_enums.push((*id, variant_tys));
+                        }
+                    },
+                    _ => {},
+                }
+            }
+
+            let mut changed = false;
+            for (id, field_tys) in pending_structs {
+                if !self.mir_code.structs.contains_key(&id) && self.register_struct(id, field_tys, arch).is_ok() {
+                    num_registered += 1;
+                    changed = true;
+                }
+            }
+            for (id, variant_tys) in pending_enums {
+                if !self.mir_code.enums.contains_key(&id) && self.register_enum(id, variant_tys, arch).is_ok() {
+                    num_registered += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return num_registered;
+            }
+        }
+    }
+
+    /// Evaluates the `SizeOf`, `StrideOf`, `AlignOf` or `OffsetOf` intrinsic, producing an integer
+    /// of type `ty`
+    pub fn eval_layout_intrinsic(&self, intr: Intrinsic, arguments: &[Const], ty: &Type, arch: Arch) -> Result<Const, LayoutError> {
+        let operand_ty = match arguments.first() {
+            Some(Const::Ty(operand_ty)) => operand_ty,
+            _ => return Err(LayoutError::InvalidOperands),
+        };
+        let lit = match intr {
+            Intrinsic::OffsetOf => {
+                let (id, index) = match (operand_ty, arguments.get(1)) {
+                    (Type::Struct(id), Some(&Const::Int { lit, .. })) => (id, lit as usize),
+                    _ => return Err(LayoutError::InvalidOperands),
+                };
+                let strukt = self.mir_code.structs.get(id).ok_or(LayoutError::UnknownLayout(*operand_ty))?;
+                *strukt.layout.field_offsets.get(index).ok_or(LayoutError::InvalidOperands)?
+            },
+            Intrinsic::SizeOf => self.layout(operand_ty, arch)?.size,
+            Intrinsic::AlignOf => self.layout(operand_ty, arch)?.alignment,
+            Intrinsic::StrideOf => self.layout(operand_ty, arch)?.stride,
+            _ => return Err(LayoutError::InvalidOperands),
+        };
+        Ok(Const::Int { lit: lit as u64, ty: *ty })
+    }
+
+    /// Like `eval::eval_intrinsic`, but also evaluates the layout intrinsics against the structs
+    /// and enums laid out so far. Types without a known layout are `EvalError::NotConstant`.
+    pub fn eval_intrinsic(&self, intr: Intrinsic, arguments: &[Const], ty: &Type, arch: Arch) -> Result<Const, EvalError> {
+        match intr {
+            Intrinsic::SizeOf | Intrinsic::StrideOf | Intrinsic::AlignOf | Intrinsic::OffsetOf => {
+                self.eval_layout_intrinsic(intr, arguments, ty, arch).map_err(|error| match error {
+                    LayoutError::UnknownLayout(_) => EvalError::NotConstant,
+                    LayoutError::InvalidOperands | LayoutError::NotSerializable(_) => EvalError::InvalidOperands,
+                })
+            },
+            _ => eval::eval_intrinsic(intr, arguments, arch),
+        }
+    }
+
+    /// Writes the in-memory representation of `konst` on `arch` to the start of `bytes`
+    fn serialize_const_into(&self, bytes: &mut [u8], konst: &Const, arch: Arch) -> Result<(), LayoutError> {
+        let endianness = arch.data_layout().endianness;
+        match konst {
+            Const::Int { lit, ty } => {
+                let size = self.layout(ty, arch)?.size;
+                write_uint(&mut bytes[..size], *lit, endianness);
+            },
+            Const::Float { lit, ty: Type::Float(FloatWidth::W32) } => write_uint(&mut bytes[..4], (*lit as f32).to_bits() as u64, endianness),
+            Const::Float { lit, ty: Type::Float(FloatWidth::W64) } => write_uint(&mut bytes[..8], lit.to_bits(), endianness),
+            &Const::Bool(val) => bytes[0] = val as u8,
+            Const::StructLit { fields, id } => {
+                let strukt = self.mir_code.structs.get(id).ok_or(LayoutError::UnknownLayout(Type::Struct(*id)))?;
+                for (field, &offset) in fields.iter().zip(&strukt.layout.field_offsets) {
+                    self.serialize_const_into(&mut bytes[offset..], field, arch)?;
+                }
+            },
+            Const::Array { elems, ty } => {
+                let elem_ty = self.types.elem(*ty).ok_or(LayoutError::NotSerializable(konst.clone()))?.ty;
+                let stride = self.layout(&elem_ty, arch)?.stride;
+                for (i, elem) in elems.iter().enumerate() {
+                    self.serialize_const_into(&mut bytes[i * stride..], elem, arch)?;
+                }
+            },
+            &Const::BasicVariant { enuum, index } => {
+                let layout = &self.mir_code.enums.get(&enuum).ok_or(LayoutError::UnknownLayout(Type::Enum(enuum)))?.layout;
+                let (offset, ty) = match layout.discriminant {
+                    DiscriminantEncoding::None => return Ok(()),
+                    DiscriminantEncoding::Tag { offset, ty } | DiscriminantEncoding::Niche { offset, ty, .. } => (offset, ty),
+                };
+                if let Some(value) = layout.encode_discriminant(index) {
+                    let size = self.layout(&ty, arch)?.size;
+                    write_uint(&mut bytes[offset..offset + size], value, endianness);
+                }
+            },
+            Const::Float { .. } | Const::Str { .. } | Const::Func { .. } | Const::Ty(_) | Const::Mod(_) => return Err(LayoutError::NotSerializable(konst.clone())),
+        }
+        Ok(())
+    }
+
+    /// The bytes that represent `konst` in memory on `arch`, with padding bytes zeroed
+    pub fn serialize_const(&self, konst: &Const, arch: Arch) -> Result<Vec<u8>, LayoutError> {
+        let ty = match *konst {
+            Const::Int { ty, .. } | Const::Float { ty, .. } | Const::Array { ty, .. } => ty,
+            Const::Bool(_) => Type::Bool,
+            Const::StructLit { id, .. } => Type::Struct(id),
+            Const::BasicVariant { enuum, .. } => Type::Enum(enuum),
+            Const::Str { .. } | Const::Func { .. } | Const::Ty(_) | Const::Mod(_) => return Err(LayoutError::NotSerializable(konst.clone())),
+        };
+        let mut bytes = vec![0; self.layout(&ty, arch)?.size];
+        self.serialize_const_into(&mut bytes, konst, arch)?;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,144 @@
+//! Loop-invariant code motion: pure instrs in a loop whose operands are all defined outside it are
+//! hoisted into the loop's preheader, so they are computed once instead of on every iteration.
+
+use std::collections::HashSet;
+
+use smallvec::SmallVec;
+
+use crate::mir::cfg::Cfg;
+use crate::mir::dom::DomTree;
+use crate::mir::loops::{LoopId, LoopInfo};
+use crate::mir::{FuncId, Instr, PhiEdge};
+use crate::source_info::SourceRange;
+use crate::{Code, Block, BlockId};
+
+impl Code {
+    /// Inserts a block before `header` that branches to it, and redirects the edges from
+    /// `entering` to `header` through the new block. Incoming phi values from `entering` are
+    /// merged with a phi in the new block if there is more than one.
+    fn insert_preheader(&mut self, func: FuncId, header: BlockId, entering: &[BlockId]) -> BlockId {
+        let preheader = self.blocks.push(Block::default());
+        let blocks = &mut self.mir_code.functions[func].blocks;
+        let position = blocks.iter().position(|&b| b == header).unwrap();
+        blocks.insert(position, preheader);
+        self.mir_code.start_block(preheader).unwrap();
+
+        for &pred in entering {
+            let terminator = *self.blocks[pred].ops.last().unwrap();
+            let mut instr = self.ops[terminator].as_mir_instr().unwrap().clone();
+            for successor in instr.successors_mut() {
+                if *successor == header {
+                    *successor = preheader;
+                }
+            }
+            self.set_mir_instr(terminator, instr);
+        }
+
+        for i in 0..self.blocks[header].ops.len() {
+            let op = self.blocks[header].ops[i];
+            let incoming = match self.ops[op].as_mir_instr() {
+                Some(Instr::Phi { incoming }) => incoming.clone(),
+                _ => break,
+            };
+            let (outside, mut inside): (SmallVec<[PhiEdge; 2]>, SmallVec<[PhiEdge; 2]>) = incoming.into_iter()
+                .partition(|edge| entering.contains(&edge.bb));
+            let value = match outside.len() {
+                0 => continue,
+                1 => outside[0].value,
+                _ => {
+                    let phi = self.push_mir_instr(Instr::Phi { incoming: outside });
+                    self.blocks[preheader].ops.push(phi);
+                    if let Some(&range) = self.mir_code.source_ranges.get(&op) {
+                        self.mir_code.source_ranges.insert(phi, range);
+                    }
+                    phi
+                },
+            };
+            inside.push(PhiEdge { bb: preheader, value });
+            self.set_mir_instr(op, Instr::Phi { incoming: inside });
+        }
+
+        let br = self.push_mir_instr(Instr::Br(header));
+        self.blocks[preheader].ops.push(br);
+        self.mir_code.source_ranges.insert(br, SourceRange::default());
+        self.mir_code.end_block(preheader).unwrap();
+        preheader
+    }
+
+    /// Gives the first loop in `func` that lacks a preheader one, returning whether there was such
+    /// a loop. Loops headed by the entry block are skipped, since they can't be given one.
+    fn insert_missing_preheader(&mut self, func: FuncId) -> bool {
+        let function = &self.mir_code.functions[func];
+        let cfg = Cfg::new(self, function);
+        let dom = DomTree::new(self, function, &cfg);
+        let loops = LoopInfo::new(&cfg, &dom);
+        for id in loops.loops().indices() {
+            let entering = loops.entering_blocks(&cfg, id);
+            if !entering.is_empty() && loops.preheader(&cfg, id).is_none() {
+                self.insert_preheader(func, loops.loops()[id].header, &entering);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Hoists the invariant instrs of the loop `id` into `preheader`, returning how many were
+    /// hoisted
+    fn hoist_invariants(&mut self, loops: &LoopInfo, id: LoopId, preheader: BlockId) -> usize {
+        let natural_loop = &loops.loops()[id];
+        let mut defined_in_loop: HashSet<_> = natural_loop.blocks.iter()
+            .flat_map(|&block| self.blocks[block].ops.iter().copied())
+            .collect();
+
+        // Blocks are in reverse postorder, so an instr's operands are seen before it is, unless it
+        // is a phi
+        let mut num_hoisted = 0;
+        for &block in &natural_loop.blocks {
+            let mut i = 0;
+            while i < self.blocks[block].ops.len() {
+                let op = self.blocks[block].ops[i];
+                let is_invariant = match self.ops[op].as_mir_instr() {
+                    // Hoisting an instr that traps could make a loop that never runs trap
+                    Some(instr) => instr.is_pure() && !instr.can_trap()
+                        && instr.operands().iter().all(|operand| !defined_in_loop.contains(operand)),
+                    None => false,
+                };
+                if is_invariant {
+                    let index = self.blocks[preheader].ops.len() - 1;
+                    self.move_op(op, block, preheader, index).unwrap();
+                    defined_in_loop.remove(&op);
+                    num_hoisted += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        num_hoisted
+    }
+
+    /// Hoists pure, loop-invariant instrs out of the loops of `func`, inserting preheaders where
+    /// loops lack them. Inner loops are handled first, so an instr can move out of several loops.
+    /// Instrs that can trap are never hoisted. Returns the number of instrs hoisted, counting an
+    /// instr once for each loop it moves out of.
+    pub fn licm(&mut self, func: FuncId) -> usize {
+        if self.mir_code.functions[func].blocks.is_empty() {
+            return 0;
+        }
+        while self.insert_missing_preheader(func) {}
+
+        let function = &self.mir_code.functions[func];
+        let cfg = Cfg::new(self, function);
+        let dom = DomTree::new(self, function, &cfg);
+        let loops = LoopInfo::new(&cfg, &dom);
+        let mut order: Vec<LoopId> = loops.loops().indices().collect();
+        order.sort_by_key(|&id| std::cmp::Reverse(loops.loops()[id].depth));
+
+        let mut num_hoisted = 0;
+        for id in order {
+            if let Some(preheader) = loops.preheader(&cfg, id) {
+                num_hoisted += self.hoist_invariants(&loops, id, preheader);
+            }
+        }
+        num_hoisted
+    }
+}
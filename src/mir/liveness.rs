@@ -0,0 +1,186 @@
+//! Liveness of MIR values: which ops hold values that may still be read, at the boundaries of each
+//! block and after each op. A phi's incoming value is live at the end of the block it comes from,
+//! not at the start of the phi's block.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Formatter};
+use std::ops::Range;
+
+use display_adapter::display_adapter;
+
+use crate::mir::cfg::Cfg;
+use crate::mir::{Function, Instr, VOID_INSTR};
+use crate::{Code, BlockId, OpId};
+
+/// The values `instr` reads in its own block: every operand except `VOID_INSTR`, and none of a
+/// phi's
+fn block_operands(instr: &Instr) -> Vec<OpId> {
+    if instr.is_phi() {
+        return Vec::new();
+    }
+    instr.operands().into_iter().filter(|&operand| operand != VOID_INSTR).collect()
+}
+
+fn write_op_set(w: &mut Formatter, ops: &HashSet<OpId>) -> fmt::Result {
+    let mut ops: Vec<OpId> = ops.iter().copied().collect();
+    ops.sort();
+    for (i, op) in ops.iter().enumerate() {
+        if i > 0 {
+            write!(w, ", ")?;
+        }
+        write!(w, "%op{}", op.index())?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Liveness {
+    live_in: HashMap<BlockId, HashSet<OpId>>,
+    live_out: HashMap<BlockId, HashSet<OpId>>,
+    /// The position of each op in the function, numbering ops consecutively in block order
+    positions: HashMap<OpId, usize>,
+    /// For each value, the positions where it is live, as sorted, disjoint ranges. A value is live
+    /// at an op that reads it and at every op after which it may still be read.
+    ranges: HashMap<OpId, Vec<Range<usize>>>,
+    max_live: usize,
+}
+
+impl Liveness {
+    pub fn new(code: &Code, func: &Function, cfg: &Cfg) -> Liveness {
+        let mut defs: HashMap<BlockId, HashSet<OpId>> = HashMap::new();
+        let mut uses: HashMap<BlockId, HashSet<OpId>> = HashMap::new();
+        let mut phi_uses: HashMap<BlockId, HashSet<OpId>> = HashMap::new();
+        for &block in &func.blocks {
+            let block_defs = defs.entry(block).or_default();
+            let block_uses = uses.entry(block).or_default();
+            for &op in &code.blocks[block].ops {
+                if let Some(instr) = code.ops[op].as_mir_instr() {
+                    for operand in block_operands(instr) {
+                        if !block_defs.contains(&operand) {
+                            block_uses.insert(operand);
+                        }
+                    }
+                    if let Instr::Phi { incoming } = instr {
+                        for edge in incoming {
+                            if edge.value != VOID_INSTR {
+                                phi_uses.entry(edge.bb).or_default().insert(edge.value);
+                            }
+                        }
+                    }
+                }
+                block_defs.insert(op);
+            }
+        }
+
+        let mut live_in: HashMap<BlockId, HashSet<OpId>> = func.blocks.iter().map(|&block| (block, HashSet::new())).collect();
+        let mut live_out = live_in.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in func.blocks.iter().rev() {
+                let mut out = phi_uses.get(&block).cloned().unwrap_or_default();
+                for successor in cfg.successors(block) {
+                    out.extend(live_in[successor].iter().copied());
+                }
+                let mut inn = uses[&block].clone();
+                inn.extend(out.difference(&defs[&block]).copied());
+                if inn != live_in[&block] || out != live_out[&block] {
+                    live_in.insert(block, inn);
+                    live_out.insert(block, out);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut liveness = Liveness {
+            live_in,
+            live_out,
+            positions: HashMap::new(),
+            ranges: HashMap::new(),
+            max_live: 0,
+        };
+        let mut position = 0;
+        for &block in &func.blocks {
+            liveness.max_live = liveness.max_live.max(liveness.live_in[&block].len());
+            let live_after = liveness.live_after_ops(code, block);
+            for (i, &op) in code.blocks[block].ops.iter().enumerate() {
+                liveness.positions.insert(op, position);
+                liveness.max_live = liveness.max_live.max(live_after[i].len());
+                let mut live_here = live_after[i].clone();
+                if let Some(instr) = code.ops[op].as_mir_instr() {
+                    live_here.extend(block_operands(instr));
+                }
+                for value in live_here {
+                    let ranges = liveness.ranges.entry(value).or_default();
+                    match ranges.last_mut() {
+                        Some(range) if range.end == position => range.end += 1,
+                        _ => ranges.push(position..position + 1),
+                    }
+                }
+                position += 1;
+            }
+        }
+        liveness
+    }
+
+    /// Values live on entry to `block`, not counting its phis
+    pub fn live_in(&self, block: BlockId) -> &HashSet<OpId> { &self.live_in[&block] }
+
+    /// Values live on exit from `block`, including incoming values of phis in its successors
+    pub fn live_out(&self, block: BlockId) -> &HashSet<OpId> { &self.live_out[&block] }
+
+    /// Whether `value` is live on exit from `block`
+    pub fn is_live_out(&self, value: OpId, block: BlockId) -> bool {
+        self.live_out(block).contains(&value)
+    }
+
+    /// The position of `op` in the function, as used by `live_range`
+    pub fn position(&self, op: OpId) -> Option<usize> {
+        self.positions.get(&op).copied()
+    }
+
+    /// The positions where `value` is live, as sorted, disjoint ranges. Empty for values that are
+    /// never read.
+    pub fn live_range(&self, value: OpId) -> &[Range<usize>] {
+        self.ranges.get(&value).map(|ranges| ranges.as_slice()).unwrap_or(&[])
+    }
+
+    /// The largest number of values live at once, on entry to a block or between two ops
+    pub fn max_live(&self) -> usize { self.max_live }
+
+    /// For each op in `block`, the values live right after it
+    pub fn live_after_ops(&self, code: &Code, block: BlockId) -> Vec<HashSet<OpId>> {
+        let ops = &code.blocks[block].ops;
+        let mut live = self.live_out[&block].clone();
+        let mut result = vec![HashSet::new(); ops.len()];
+        for (i, &op) in ops.iter().enumerate().rev() {
+            result[i] = live.clone();
+            live.remove(&op);
+            if let Some(instr) = code.ops[op].as_mir_instr() {
+                live.extend(block_operands(instr));
+            }
+        }
+        result
+    }
+}
+
+impl Code {
+    /// Displays `block` like `display_block`, with the values live on entry and exit, and after
+    /// each op. The output is for debugging, and can't be read back by `parse_mir`.
+    #[display_adapter]
+    pub fn display_block_liveness(&self, block: BlockId, liveness: &Liveness, w: &mut Formatter) {
+        write!(w, "    // live in: ")?;
+        write_op_set(w, liveness.live_in(block))?;
+        writeln!(w)?;
+        let ops = self.display_block(block).to_string();
+        let live_after = liveness.live_after_ops(self, block);
+        for (line, live) in ops.lines().zip(&live_after) {
+            write!(w, "{:<48} // live: ", line)?;
+            write_op_set(w, live)?;
+            writeln!(w)?;
+        }
+        write!(w, "    // live out: ")?;
+        write_op_set(w, liveness.live_out(block))?;
+        writeln!(w)
+    }
+}
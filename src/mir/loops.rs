@@ -0,0 +1,125 @@
+//! Natural loop detection. A back edge is an edge whose target dominates its source; the loop of a
+//! back edge is its target, the loop header, plus every block that can reach the back edge without
+//! going through the header. Back edges to the same header form a single loop.
+
+use std::collections::{HashMap, HashSet};
+
+use index_vec::{IndexVec, define_index_type};
+
+use crate::mir::cfg::Cfg;
+use crate::mir::dom::DomTree;
+use crate::BlockId;
+
+define_index_type!(pub struct LoopId = u32;);
+
+#[derive(Debug)]
+pub struct Loop {
+    pub header: BlockId,
+    /// The sources of the back edges to `header`
+    pub latches: Vec<BlockId>,
+    /// Every block in the loop, including `header` and the blocks of nested loops, in reverse
+    /// postorder
+    pub blocks: Vec<BlockId>,
+    /// The innermost loop containing this one
+    pub parent: Option<LoopId>,
+    /// 1 for outermost loops, 2 for loops nested in those, and so on
+    pub depth: usize,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.blocks.contains(&block)
+    }
+}
+
+/// The natural loops of one function
+#[derive(Debug)]
+pub struct LoopInfo {
+    loops: IndexVec<LoopId, Loop>,
+    /// The innermost loop containing each block that is in a loop
+    innermost: HashMap<BlockId, LoopId>,
+}
+
+impl LoopInfo {
+    /// Finds the loops among the blocks reachable from the entry block
+    pub fn new(cfg: &Cfg, dom: &DomTree) -> LoopInfo {
+        let rpo_index: HashMap<BlockId, usize> = cfg.reverse_postorder().iter()
+            .enumerate()
+            .map(|(i, &block)| (block, i))
+            .collect();
+
+        let mut loops: IndexVec<LoopId, Loop> = IndexVec::new();
+        for &header in cfg.reverse_postorder() {
+            let latches: Vec<BlockId> = cfg.predecessors(header).iter()
+                .copied()
+                .filter(|&pred| cfg.is_reachable(pred) && dom.dominates(header, pred))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+
+            // Walk backwards from the latches, stopping at the header
+            let mut body = HashSet::new();
+            body.insert(header);
+            let mut stack = latches.clone();
+            while let Some(block) = stack.pop() {
+                if body.insert(block) {
+                    stack.extend(cfg.predecessors(block).iter().copied().filter(|&pred| cfg.is_reachable(pred)));
+                }
+            }
+            let mut blocks: Vec<BlockId> = body.into_iter().collect();
+            blocks.sort_by_key(|block| rpo_index[block]);
+            loops.push(Loop { header, latches, blocks, parent: None, depth: 1 });
+        }
+
+        // Headers are visited in reverse postorder, so a loop's header comes after the headers of
+        // every loop containing it. The innermost one is therefore the last.
+        let mut innermost = HashMap::new();
+        for id in loops.indices() {
+            let parent = (0..id.index()).rev()
+                .map(LoopId::new)
+                .find(|&other| loops[other].contains(loops[id].header));
+            loops[id].parent = parent;
+            loops[id].depth = parent.map(|parent| loops[parent].depth + 1).unwrap_or(1);
+            for &block in &loops[id].blocks {
+                innermost.insert(block, id);
+            }
+        }
+
+        LoopInfo { loops, innermost }
+    }
+
+    pub fn loops(&self) -> &IndexVec<LoopId, Loop> { &self.loops }
+
+    /// The innermost loop containing `block`
+    pub fn loop_of(&self, block: BlockId) -> Option<LoopId> {
+        self.innermost.get(&block).copied()
+    }
+
+    /// The number of loops containing `block`
+    pub fn depth(&self, block: BlockId) -> usize {
+        self.loop_of(block).map(|id| self.loops[id].depth).unwrap_or(0)
+    }
+
+    pub fn is_header(&self, block: BlockId) -> bool {
+        self.loop_of(block).map(|id| self.loops[id].header == block).unwrap_or(false)
+    }
+
+    /// The predecessors of the loop's header from outside the loop
+    pub fn entering_blocks(&self, cfg: &Cfg, id: LoopId) -> Vec<BlockId> {
+        let natural_loop = &self.loops[id];
+        cfg.predecessors(natural_loop.header).iter()
+            .copied()
+            .filter(|&pred| cfg.is_reachable(pred) && !natural_loop.contains(pred))
+            .collect()
+    }
+
+    /// The block that is the only way into the loop, if there is one: the header's only
+    /// predecessor from outside the loop, when the header is that block's only successor
+    pub fn preheader(&self, cfg: &Cfg, id: LoopId) -> Option<BlockId> {
+        match self.entering_blocks(cfg, id).as_slice() {
+            &[pred] if cfg.successors(pred) == [self.loops[id].header] => Some(pred),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Promotes `Alloca` slots whose address never escapes to SSA values, using the classic
+//! dominance-frontier phi placement followed by a renaming walk over the dominator tree.
+
+use std::collections::{HashMap, HashSet};
+
+use smallvec::SmallVec;
+
+use crate::mir::cfg::Cfg;
+use crate::mir::dom::{DomTree, DominanceFrontiers};
+use crate::mir::{FuncId, Instr, PhiEdge, VOID_INSTR};
+use crate::{Code, BlockId, OpId};
+
+impl Code {
+    /// Returns whether every use of `alloca` is a `Load` from it or a `Store` to it
+    fn is_promotable(&self, alloca: OpId) -> bool {
+        self.uses(alloca).iter().all(|&user| match self.ops[user].as_mir_instr() {
+            Some(&Instr::Load(location)) => location == alloca,
+            Some(&Instr::Store { location, value }) => location == alloca && value != alloca,
+            _ => false,
+        })
+    }
+
+    /// Promotes the promotable allocas of `func` to SSA values, returning how many were promoted.
+    /// A load that isn't preceded by a store on some path reads `VOID_INSTR` along that path.
+    pub fn mem2reg(&mut self, func: FuncId) -> usize {
+        let function = &self.mir_code.functions[func];
+        if function.blocks.is_empty() {
+            return 0;
+        }
+        let cfg = Cfg::new(self, function);
+        let dom = DomTree::new(self, function, &cfg);
+        let frontiers = DominanceFrontiers::new(&cfg, &dom);
+
+        let mut op_blocks = HashMap::new();
+        let mut allocas = Vec::new();
+        for &block in &function.blocks {
+            for &op in &self.blocks[block].ops {
+                op_blocks.insert(op, block);
+                if let Some(Instr::Alloca(_)) = self.ops[op].as_mir_instr() {
+                    if self.is_promotable(op) {
+                        allocas.push(op);
+                    }
+                }
+            }
+        }
+
+        // Place phis at the iterated dominance frontier of each alloca's stores. Allocas that would
+        // need a phi in the entry block are left alone, since the entry block can't start with phis.
+        let mut phis: Vec<(BlockId, OpId)> = Vec::new();
+        let mut promoted = Vec::new();
+        for &alloca in &allocas {
+            let mut worklist: Vec<BlockId> = self.uses(alloca).iter()
+                .filter(|&&user| matches!(self.ops[user].as_mir_instr(), Some(Instr::Store { .. })))
+                .map(|user| op_blocks[user])
+                .filter(|&block| cfg.is_reachable(block))
+                .collect();
+            let mut phi_blocks = SmallVec::<[BlockId; 2]>::new();
+            let mut visited: HashSet<BlockId> = HashSet::new();
+            while let Some(block) = worklist.pop() {
+                for &frontier in frontiers.frontier(block) {
+                    if visited.insert(frontier) {
+                        phi_blocks.push(frontier);
+                        worklist.push(frontier);
+                    }
+                }
+            }
+            if phi_blocks.contains(&cfg.entry()) {
+                continue;
+            }
+            promoted.push(alloca);
+            for block in phi_blocks {
+                phis.push((block, alloca));
+            }
+        }
+        if promoted.is_empty() {
+            return 0;
+        }
+        let promoted_set: HashSet<OpId> = promoted.iter().copied().collect();
+
+        // Maps each new phi to its alloca and block
+        let mut phi_ops: HashMap<OpId, (OpId, BlockId)> = HashMap::new();
+        for (block, alloca) in phis {
+            let phi = self.push_mir_instr(Instr::Phi { incoming: SmallVec::new() });
+            self.blocks[block].ops.insert(0, phi);
+            if let Some(&range) = self.mir_code.source_ranges.get(&alloca) {
+                self.mir_code.source_ranges.insert(phi, range);
+            }
+            if let Some(name) = self.mir_code.instr_names.get(&alloca).cloned() {
+                let name = self.mir_code.functions[func].instr_namespace.insert(name);
+                self.mir_code.instr_names.insert(phi, name);
+            }
+            phi_ops.insert(phi, (alloca, block));
+        }
+
+        // Walk the dominator tree, tracking the current value of each promoted alloca
+        let mut incoming: HashMap<OpId, SmallVec<[PhiEdge; 2]>> = HashMap::new();
+        let mut dead = Vec::new();
+        let initial: HashMap<OpId, OpId> = promoted.iter().map(|&alloca| (alloca, VOID_INSTR)).collect();
+        let mut stack = vec![(cfg.entry(), initial)];
+        while let Some((block, mut values)) = stack.pop() {
+            for i in 0..self.blocks[block].ops.len() {
+                let op = self.blocks[block].ops[i];
+                match *self.ops[op].as_mir_instr().unwrap_or(&Instr::Void) {
+                    Instr::Phi { .. } => if let Some(&(alloca, _)) = phi_ops.get(&op) {
+                        values.insert(alloca, op);
+                    },
+                    Instr::Load(location) if promoted_set.contains(&location) => {
+                        self.replace_all_uses(op, values[&location]);
+                        dead.push((block, op));
+                    },
+                    Instr::Store { location, value } if promoted_set.contains(&location) => {
+                        values.insert(location, value);
+                        dead.push((block, op));
+                    },
+                    _ => {},
+                }
+            }
+            for &successor in cfg.successors(block) {
+                for &op in &self.blocks[successor].ops {
+                    match phi_ops.get(&op) {
+                        Some((alloca, _)) => incoming.entry(op).or_default().push(PhiEdge { bb: block, value: values[alloca] }),
+                        None => if !matches!(self.ops[op].as_mir_instr(), Some(Instr::Phi { .. })) {
+                            break;
+                        },
+                    }
+                }
+            }
+            for &child in dom.children(block) {
+                stack.push((child, values.clone()));
+            }
+        }
+
+        // Loads and stores in unreachable blocks never run, so their values are never observed
+        for &block in &cfg.unreachable_blocks() {
+            for &op in &self.blocks[block].ops {
+                match *self.ops[op].as_mir_instr().unwrap_or(&Instr::Void) {
+                    Instr::Load(location) | Instr::Store { location, .. } if promoted_set.contains(&location) => {
+                        dead.push((block, op));
+                    },
+                    _ => {},
+                }
+            }
+        }
+        // Unreachable predecessors also need an incoming edge
+        for (&phi, &(_, block)) in &phi_ops {
+            let mut edges = incoming.remove(&phi).unwrap_or_default();
+            for &pred in cfg.predecessors(block) {
+                if !cfg.is_reachable(pred) {
+                    edges.push(PhiEdge { bb: pred, value: VOID_INSTR });
+                }
+            }
+            self.set_mir_instr(phi, Instr::Phi { incoming: edges });
+        }
+
+        for (block, op) in dead {
+            self.replace_all_uses(op, VOID_INSTR);
+            self.erase_op(block, op).unwrap();
+        }
+        for &alloca in &promoted {
+            self.erase_op(op_blocks[&alloca], alloca).unwrap();
+        }
+        promoted.len()
+    }
+}
@@ -0,0 +1,861 @@
+//! Parser for the textual MIR syntax described in `mir::text`.
+
+use std::collections::HashSet;
+use std::ffi::CString;
+
+use smallvec::SmallVec;
+use string_interner::StringInterner;
+
+use crate::hir::{Intrinsic, DeclId, StructId, EnumId, ModScopeId, GenericParamId};
+use crate::mir::{Const, Function, Instr, Static, SwitchCase, PhiEdge, FuncId, StaticId, StrId, VOID_INSTR};
+use crate::source_info::SourceRange;
+use crate::ty::Type;
+use crate::{Code, Block, BlockId, OpId};
+
+#[derive(Debug)]
+pub struct ParseError {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    /// A `%`-prefixed name, stored without the `%`
+    Local(String),
+    Num(String),
+    Str(Vec<u8>),
+    Punct(char),
+    Arrow,
+    FatArrow,
+    DotDot,
+    Eof,
+}
+
+struct Lexed {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn lex(src: &str) -> Result<Vec<Lexed>, ParseError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let column = i - line_start + 1;
+        let error = |message: &str| Err(ParseError { line, column, message: message.to_string() });
+        if c == b'\n' {
+            i += 1;
+            line += 1;
+            line_start = i;
+            continue;
+        } else if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if bytes[i..].starts_with(b"//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let token = if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && is_ident_char(bytes[i]) {
+                i += 1;
+            }
+            Token::Ident(src[start..i].to_string())
+        } else if c == b'%' {
+            i += 1;
+            let start = i;
+            while i < bytes.len() && is_ident_char(bytes[i]) {
+                i += 1;
+            }
+            if start == i {
+                return error("expected name after `%`");
+            }
+            Token::Local(src[start..i].to_string())
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i + 1 < bytes.len() && bytes[i] == b'.' && bytes[i + 1].is_ascii_digit() {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            Token::Num(src[start..i].to_string())
+        } else if c == b'"' {
+            i += 1;
+            let mut string = Vec::new();
+            loop {
+                match bytes.get(i) {
+                    None | Some(b'\n') => return error("unterminated string literal"),
+                    Some(b'"') => {
+                        i += 1;
+                        break;
+                    },
+                    Some(b'\\') => {
+                        let escaped = match bytes.get(i + 1) {
+                            Some(b'\\') => b'\\',
+                            Some(b'"') => b'"',
+                            Some(b'n') => b'\n',
+                            Some(b't') => b'\t',
+                            Some(b'r') => b'\r',
+                            Some(b'x') => {
+                                let hex = src.get(i + 2..i + 4).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                                match hex {
+                                    Some(byte) => {
+                                        string.push(byte);
+                                        i += 4;
+                                        continue;
+                                    },
+                                    None => return error("invalid `\\x` escape"),
+                                }
+                            },
+                            _ => return error("invalid escape sequence"),
+                        };
+                        string.push(escaped);
+                        i += 2;
+                    },
+                    Some(&byte) => {
+                        string.push(byte);
+                        i += 1;
+                    },
+                }
+            }
+            Token::Str(string)
+        } else if bytes[i..].starts_with(b"->") {
+            i += 2;
+            Token::Arrow
+        } else if bytes[i..].starts_with(b"=>") {
+            i += 2;
+            Token::FatArrow
+        } else if bytes[i..].starts_with(b"..") {
+            i += 2;
+            Token::DotDot
+        } else if b"=:;,()<>{}[]*.-@".contains(&c) {
+            i += 1;
+            Token::Punct(c as char)
+        } else {
+            return error(&format!("unexpected character `{}`", c as char));
+        };
+        tokens.push(Lexed { token, line, column });
+    }
+    tokens.push(Lexed { token: Token::Eof, line, column: bytes.len() - line_start + 1 });
+    Ok(tokens)
+}
+
+/// A reference to an entity that must be defined somewhere in the module
+enum Reference {
+    Op(OpId),
+    Block(BlockId),
+    Func(FuncId),
+    Str(StrId),
+    Static(StaticId),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Lexed>,
+    pos: usize,
+    code: Code,
+    interner: &'a mut StringInterner,
+    defined_ops: HashSet<OpId>,
+    defined_blocks: HashSet<BlockId>,
+    references: Vec<(Reference, usize, usize)>,
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].token
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].token.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        let lexed = &self.tokens[self.pos];
+        Err(ParseError { line: lexed.line, column: lexed.column, message: message.into() })
+    }
+
+    fn reference(&mut self, reference: Reference) {
+        let lexed = &self.tokens[self.pos.saturating_sub(1)];
+        self.references.push((reference, lexed.line, lexed.column));
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        *self.peek() == Token::Punct(c)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(ident) if ident == keyword)
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.is_punct(c);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn expect_token(&mut self, token: Token, description: &str) -> ParseResult<()> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            self.error(format!("expected {}", description))
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> ParseResult<()> {
+        self.expect_token(Token::Punct(c), &format!("`{}`", c))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{}`", keyword))
+        }
+    }
+
+    fn expect_ident(&mut self) -> ParseResult<String> {
+        match self.peek().clone() {
+            Token::Ident(ident) => {
+                self.next();
+                Ok(ident)
+            },
+            _ => self.error("expected identifier"),
+        }
+    }
+
+    fn expect_num(&mut self) -> ParseResult<usize> {
+        match self.peek().clone() {
+            Token::Num(num) => match num.parse() {
+                Ok(num) => {
+                    self.next();
+                    Ok(num)
+                },
+                Err(_) => self.error("expected integer"),
+            },
+            _ => self.error("expected integer"),
+        }
+    }
+
+    fn expect_str(&mut self) -> ParseResult<Vec<u8>> {
+        match self.peek().clone() {
+            Token::Str(string) => {
+                self.next();
+                Ok(string)
+            },
+            _ => self.error("expected string literal"),
+        }
+    }
+
+    fn expect_utf8_str(&mut self) -> ParseResult<String> {
+        let bytes = self.expect_str()?;
+        match String::from_utf8(bytes) {
+            Ok(string) => Ok(string),
+            Err(_) => self.error("expected valid UTF-8 in string literal"),
+        }
+    }
+
+    /// Parses a name made of `prefix` followed by a decimal index, like `struct3`
+    fn indexed_name(name: &str, prefix: &str) -> Option<usize> {
+        let digits = name.strip_prefix(prefix)?;
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+
+    fn expect_local(&mut self, prefix: &str) -> ParseResult<usize> {
+        if let Token::Local(name) = self.peek() {
+            if let Some(index) = Self::indexed_name(name, prefix) {
+                self.next();
+                return Ok(index);
+            }
+        }
+        self.error(format!("expected `%{}N`", prefix))
+    }
+
+    fn expect_indexed_ident(&mut self, prefix: &str) -> ParseResult<usize> {
+        if let Token::Ident(name) = self.peek() {
+            if let Some(index) = Self::indexed_name(name, prefix) {
+                self.next();
+                return Ok(index);
+            }
+        }
+        self.error(format!("expected `{}N`", prefix))
+    }
+
+    fn op_ref(&mut self) -> ParseResult<OpId> {
+        let op = OpId::new(self.expect_local("op")?);
+        self.reference(Reference::Op(op));
+        Ok(op)
+    }
+
+    fn block_ref(&mut self) -> ParseResult<BlockId> {
+        let block = BlockId::new(self.expect_local("bb")?);
+        self.reference(Reference::Block(block));
+        Ok(block)
+    }
+
+    fn parse_type(&mut self) -> ParseResult<Type> {
+        if self.eat_punct('*') {
+            let is_mut = self.eat_keyword("mut");
+            let pointee = self.parse_type()?;
+            return Ok(self.code.types.ptr_with_mut(pointee, is_mut));
+        }
+        if self.eat_punct('[') {
+            if self.eat_punct(']') {
+                let is_mut = self.eat_keyword("mut");
+                let elem = self.parse_type()?;
+                return Ok(self.code.types.slice(elem, is_mut));
+            }
+            let elem = self.parse_type()?;
+            self.expect_punct(';')?;
+            let len = self.expect_num()?;
+            self.expect_punct(']')?;
+            return Ok(self.code.types.array(elem, len));
+        }
+        if self.eat_keyword("fn") {
+            self.expect_punct('(')?;
+            let mut params = Vec::new();
+            if !self.eat_punct(')') {
+                loop {
+                    params.push(self.parse_type()?);
+                    if self.eat_punct(')') {
+                        break;
+                    }
+                    self.expect_punct(',')?;
+                }
+            }
+            self.expect_token(Token::Arrow, "`->`")?;
+            let ret = self.parse_type()?;
+            return Ok(self.code.types.function(&params, ret));
+        }
+        let name = match self.peek() {
+            Token::Ident(name) => name.clone(),
+            _ => return self.error("expected type"),
+        };
+        let ty = match name.as_str() {
+            "error" => Type::Error,
+            "never" => Type::Never,
+            "bool" => Type::Bool,
+            "void" => Type::Void,
+            "module" => Type::Mod,
+            "type" => Type::Ty,
+            "i8" => Type::i8(),
+            "i16" => Type::i16(),
+            "i32" => Type::i32(),
+            "i64" => Type::i64(),
+            "isize" => Type::isize(),
+            "u8" => Type::u8(),
+            "u16" => Type::u16(),
+            "u32" => Type::u32(),
+            "u64" => Type::u64(),
+            "usize" => Type::usize(),
+            "f32" => Type::f32(),
+            "f64" => Type::f64(),
+            _ => if let Some(index) = Self::indexed_name(&name, "struct") {
+                Type::Struct(StructId::new(index))
+            } else if let Some(index) = Self::indexed_name(&name, "enum") {
+                Type::Enum(EnumId::new(index))
+            } else if let Some(index) = Self::indexed_name(&name, "generic_param") {
+                Type::GenericParam(GenericParamId::new(index))
+            } else {
+                return self.error(format!("unknown type `{}`", name));
+            },
+        };
+        self.next();
+        Ok(ty)
+    }
+
+    fn parse_float(&mut self) -> ParseResult<f64> {
+        let negative = self.eat_punct('-');
+        let text = match self.peek() {
+            Token::Num(num) => num.clone(),
+            Token::Ident(ident) if ident == "inf" || ident == "NaN" => ident.clone(),
+            _ => return self.error("expected float literal"),
+        };
+        let lit: f64 = match text.parse() {
+            Ok(lit) => lit,
+            Err(_) => return self.error("expected float literal"),
+        };
+        self.next();
+        Ok(if negative { -lit } else { lit })
+    }
+
+    fn parse_const(&mut self) -> ParseResult<Const> {
+        let kind = self.expect_ident()?;
+        let konst = match kind.as_str() {
+            "int" => {
+                let ty = self.parse_type()?;
+                let lit = match self.peek() {
+                    Token::Num(num) => num.parse::<u64>().ok(),
+                    _ => None,
+                };
+                match lit {
+                    Some(lit) => {
+                        self.next();
+                        Const::Int { lit, ty }
+                    },
+                    None => return self.error("expected integer literal"),
+                }
+            },
+            "float" => {
+                let ty = self.parse_type()?;
+                let lit = self.parse_float()?;
+                Const::Float { lit, ty }
+            },
+            "str" => {
+                let ty = self.parse_type()?;
+                let id = StrId::new(self.expect_local("str")?);
+                self.reference(Reference::Str(id));
+                Const::Str { id, ty }
+            },
+            "func" => {
+                let ty = self.parse_type()?;
+                self.expect_punct('@')?;
+                let id = FuncId::new(self.expect_indexed_ident("f")?);
+                self.reference(Reference::Func(id));
+                Const::Func { id, ty }
+            },
+            "bool" => if self.eat_keyword("true") {
+                Const::Bool(true)
+            } else if self.eat_keyword("false") {
+                Const::Bool(false)
+            } else {
+                return self.error("expected `true` or `false`");
+            },
+            "type" => Const::Ty(self.parse_type()?),
+            "mod" => Const::Mod(ModScopeId::new(self.expect_local("mod")?)),
+            "variant" => {
+                let enuum = EnumId::new(self.expect_indexed_ident("enum")?);
+                let index = self.expect_num()?;
+                Const::BasicVariant { enuum, index }
+            },
+            "struct" => {
+                let id = StructId::new(self.expect_indexed_ident("struct")?);
+                self.expect_punct('{')?;
+                let mut fields = Vec::new();
+                if !self.eat_punct('}') {
+                    loop {
+                        fields.push(self.parse_const()?);
+                        if self.eat_punct('}') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                Const::StructLit { fields, id }
+            },
+            "array" => {
+                let ty = self.parse_type()?;
+                self.expect_punct('[')?;
+                let mut elems = Vec::new();
+                if !self.eat_punct(']') {
+                    loop {
+                        elems.push(self.parse_const()?);
+                        if self.eat_punct(']') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                Const::Array { elems, ty }
+            },
+            _ => return self.error(format!("unknown constant kind `{}`", kind)),
+        };
+        Ok(konst)
+    }
+
+    fn parse_operands(&mut self) -> ParseResult<SmallVec<[OpId; 2]>> {
+        self.expect_punct('(')?;
+        let mut operands = SmallVec::new();
+        if !self.eat_punct(')') {
+            loop {
+                operands.push(self.op_ref()?);
+                if self.eat_punct(')') {
+                    break;
+                }
+                self.expect_punct(',')?;
+            }
+        }
+        Ok(operands)
+    }
+
+    fn parse_cast(&mut self, cast: fn(OpId, Type) -> Instr) -> ParseResult<Instr> {
+        let op = self.op_ref()?;
+        self.expect_keyword("to")?;
+        let ty = self.parse_type()?;
+        Ok(cast(op, ty))
+    }
+
+    fn parse_instr(&mut self) -> ParseResult<Instr> {
+        let mnemonic = self.expect_ident()?;
+        let instr = match mnemonic.as_str() {
+            "void" => Instr::Void,
+            "const" => Instr::Const(self.parse_const()?),
+            "alloca" => Instr::Alloca(self.parse_type()?),
+            "logical_not" => Instr::LogicalNot(self.op_ref()?),
+            "call" => {
+                self.expect_punct('@')?;
+                let func = FuncId::new(self.expect_indexed_ident("f")?);
+                self.reference(Reference::Func(func));
+                let mut generic_arguments = Vec::new();
+                if self.eat_punct('<') {
+                    loop {
+                        generic_arguments.push(self.parse_type()?);
+                        if self.eat_punct('>') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                let arguments = self.parse_operands()?;
+                Instr::Call { arguments, generic_arguments, func }
+            },
+            "call_indirect" => {
+                let callee = self.op_ref()?;
+                let arguments = self.parse_operands()?;
+                Instr::CallIndirect { arguments, callee }
+            },
+            "intrinsic" => {
+                let name = self.expect_ident()?;
+                let intr = match Intrinsic::from_mir_name(&name) {
+                    Some(intr) => intr,
+                    None => return self.error(format!("unknown intrinsic `{}`", name)),
+                };
+                let ty = self.parse_type()?;
+                let arguments = self.parse_operands()?;
+                Instr::Intrinsic { arguments, ty, intr }
+            },
+            "reinterpret" => self.parse_cast(Instr::Reinterpret)?,
+            "truncate" => self.parse_cast(Instr::Truncate)?,
+            "sign_extend" => self.parse_cast(Instr::SignExtend)?,
+            "zero_extend" => self.parse_cast(Instr::ZeroExtend)?,
+            "float_cast" => self.parse_cast(Instr::FloatCast)?,
+            "float_to_int" => self.parse_cast(Instr::FloatToInt)?,
+            "int_to_float" => self.parse_cast(Instr::IntToFloat)?,
+            "load" => Instr::Load(self.op_ref()?),
+            "store" => {
+                let value = self.op_ref()?;
+                self.expect_keyword("to")?;
+                let location = self.op_ref()?;
+                Instr::Store { location, value }
+            },
+            "address_of_static" => {
+                let id = StaticId::new(self.expect_local("static")?);
+                self.reference(Reference::Static(id));
+                Instr::AddressOfStatic(id)
+            },
+            "pointer" => {
+                let is_mut = self.eat_keyword("mut");
+                let op = self.op_ref()?;
+                Instr::Pointer { op, is_mut }
+            },
+            "struct" => {
+                let id = StructId::new(self.expect_indexed_ident("struct")?);
+                let fields = self.parse_operands()?;
+                Instr::Struct { fields, id }
+            },
+            "enum" => {
+                let id = EnumId::new(self.expect_indexed_ident("enum")?);
+                let variants = self.parse_operands()?;
+                Instr::Enum { variants, id }
+            },
+            "struct_lit" => {
+                let id = StructId::new(self.expect_indexed_ident("struct")?);
+                let fields = self.parse_operands()?;
+                Instr::StructLit { fields, id }
+            },
+            "direct_field_access" | "indirect_field_access" => {
+                let val = self.op_ref()?;
+                self.expect_punct(',')?;
+                let index = self.expect_num()?;
+                if mnemonic == "direct_field_access" {
+                    Instr::DirectFieldAccess { val, index }
+                } else {
+                    Instr::IndirectFieldAccess { val, index }
+                }
+            },
+            "variant" => {
+                let enuum = EnumId::new(self.expect_indexed_ident("enum")?);
+                self.expect_punct(',')?;
+                let index = self.expect_num()?;
+                self.expect_punct(',')?;
+                let payload = self.op_ref()?;
+                Instr::Variant { enuum, index, payload }
+            },
+            "discriminant_access" => Instr::DiscriminantAccess { val: self.op_ref()? },
+            "element_access" | "element_address" | "make_slice" => {
+                let first = self.op_ref()?;
+                self.expect_punct(',')?;
+                let second = self.op_ref()?;
+                match mnemonic.as_str() {
+                    "element_access" => Instr::ElementAccess { val: first, index: second },
+                    "element_address" => Instr::ElementAddress { ptr: first, index: second },
+                    _ => Instr::MakeSlice { ptr: first, len: second },
+                }
+            },
+            "slice_len" => Instr::SliceLen(self.op_ref()?),
+            "ret" => Instr::Ret(self.op_ref()?),
+            "br" => Instr::Br(self.block_ref()?),
+            "cond_br" => {
+                let condition = self.op_ref()?;
+                self.expect_punct(',')?;
+                let true_bb = self.block_ref()?;
+                self.expect_punct(',')?;
+                let false_bb = self.block_ref()?;
+                Instr::CondBr { condition, true_bb, false_bb }
+            },
+            "switch_br" => {
+                let scrutinee = self.op_ref()?;
+                self.expect_punct('[')?;
+                let mut cases = Vec::new();
+                if !self.eat_punct(']') {
+                    loop {
+                        let value = self.parse_const()?;
+                        self.expect_token(Token::FatArrow, "`=>`")?;
+                        let bb = self.block_ref()?;
+                        cases.push(SwitchCase { value, bb });
+                        if self.eat_punct(']') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                self.expect_keyword("else")?;
+                let catch_all_bb = self.block_ref()?;
+                Instr::SwitchBr { scrutinee, cases, catch_all_bb }
+            },
+            "generic_param" => Instr::GenericParam(GenericParamId::new(self.expect_indexed_ident("generic_param")?)),
+            "parameter" => Instr::Parameter(self.parse_type()?),
+            "phi" => {
+                self.expect_punct('[')?;
+                let mut incoming = SmallVec::new();
+                if !self.eat_punct(']') {
+                    loop {
+                        let bb = self.block_ref()?;
+                        self.expect_punct(':')?;
+                        let value = self.op_ref()?;
+                        incoming.push(PhiEdge { bb, value });
+                        if self.eat_punct(']') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                Instr::Phi { incoming }
+            },
+            _ => return self.error(format!("unknown instruction `{}`", mnemonic)),
+        };
+        Ok(instr)
+    }
+
+    fn parse_op(&mut self, func: &mut Function, block: BlockId) -> ParseResult<()> {
+        let id = OpId::new(self.expect_local("op")?);
+        // `VOID_INSTR` is shared by every function, so each one that uses it defines it
+        if !self.defined_ops.insert(id) && id != VOID_INSTR {
+            return self.error(format!("%op{} is defined more than once", id.index()));
+        }
+        let name = if let Token::Str(_) = self.peek() {
+            Some(self.expect_utf8_str()?)
+        } else {
+            None
+        };
+        self.expect_punct('=')?;
+        self.expect_keyword("mir")?;
+        self.expect_punct('.')?;
+        let instr = self.parse_instr()?;
+        if id == VOID_INSTR && instr != Instr::Void {
+            return self.error("%op0 is reserved for the void instruction");
+        }
+        let mut range = SourceRange::default();
+        if self.eat_punct('@') {
+            range.start = self.expect_num()?;
+            self.expect_token(Token::DotDot, "`..`")?;
+            range.end = self.expect_num()?;
+        }
+
+        while self.code.ops.len() <= id.index() {
+            self.code.push_mir_instr(Instr::Void);
+        }
+        self.code.set_mir_instr(id, instr);
+        self.code.blocks[block].ops.push(id);
+        self.code.mir_code.source_ranges.insert(id, range);
+        if let Some(name) = name {
+            func.instr_namespace.reserve(name.clone());
+            self.code.mir_code.instr_names.insert(id, name);
+        }
+        Ok(())
+    }
+
+    fn parse_block(&mut self, func: &mut Function) -> ParseResult<()> {
+        let block = BlockId::new(self.expect_local("bb")?);
+        if !self.defined_blocks.insert(block) {
+            return self.error(format!("%bb{} is defined more than once", block.index()));
+        }
+        self.expect_punct(':')?;
+        while self.code.blocks.len() <= block.index() {
+            self.code.blocks.push(Block::default());
+        }
+        func.blocks.push(block);
+        while matches!(self.peek(), Token::Local(name) if name.starts_with("op")) {
+            self.parse_op(func, block)?;
+        }
+        self.code.mir_code.start_block(block).unwrap();
+        self.code.mir_code.end_block(block).unwrap();
+        Ok(())
+    }
+
+    fn parse_function(&mut self) -> ParseResult<()> {
+        self.expect_keyword("fn")?;
+        let id = self.code.mir_code.functions.next_idx();
+        if self.eat_punct('@') && self.expect_indexed_ident("f")? != id.index() {
+            return self.error(format!("expected function to be numbered @f{}", id.index()));
+        }
+        let mut func = Function::default();
+        if let Token::Str(_) = self.peek() {
+            let name = self.expect_utf8_str()?;
+            func.name = Some(self.interner.get_or_intern(name));
+        }
+        if self.eat_punct('<') {
+            loop {
+                func.generic_params.push(GenericParamId::new(self.expect_indexed_ident("generic_param")?));
+                if self.eat_punct('>') {
+                    break;
+                }
+                self.expect_punct(',')?;
+            }
+        }
+        self.expect_token(Token::Arrow, "`->`")?;
+        func.ret_ty = self.parse_type()?;
+        if self.eat_keyword("decl") {
+            func.decl = Some(DeclId::new(self.expect_local("decl")?));
+        }
+        self.expect_punct('{')?;
+        while !self.eat_punct('}') {
+            self.parse_block(&mut func)?;
+        }
+        self.code.mir_code.functions.push(func);
+        Ok(())
+    }
+
+    fn parse_string(&mut self) -> ParseResult<()> {
+        self.expect_keyword("string")?;
+        let id = self.expect_local("str")?;
+        if id != self.code.mir_code.strings.len() {
+            return self.error(format!("expected string to be numbered %str{}", self.code.mir_code.strings.len()));
+        }
+        self.expect_punct('=')?;
+        let string = match CString::new(self.expect_str()?) {
+            Ok(string) => string,
+            Err(_) => return self.error("string literals may not contain NUL bytes"),
+        };
+        self.code.mir_code.strings.push(string);
+        Ok(())
+    }
+
+    fn parse_static(&mut self) -> ParseResult<()> {
+        self.expect_keyword("static")?;
+        let id = self.expect_local("static")?;
+        if id != self.code.mir_code.statics.len() {
+            return self.error(format!("expected static to be numbered %static{}", self.code.mir_code.statics.len()));
+        }
+        let name = self.expect_utf8_str()?;
+        self.expect_punct('=')?;
+        let val = self.parse_const()?;
+        self.code.mir_code.statics.push(Static { name, val });
+        Ok(())
+    }
+
+    fn check_references(&self) -> ParseResult<()> {
+        for &(ref reference, line, column) in &self.references {
+            let (defined, name) = match *reference {
+                Reference::Op(op) => (op == VOID_INSTR || self.defined_ops.contains(&op), format!("%op{}", op.index())),
+                Reference::Block(block) => (self.defined_blocks.contains(&block), format!("%bb{}", block.index())),
+                Reference::Func(func) => (func.index() < self.code.mir_code.functions.len(), format!("@f{}", func.index())),
+                Reference::Str(id) => (id.index() < self.code.mir_code.strings.len(), format!("%str{}", id.index())),
+                Reference::Static(id) => (id.index() < self.code.mir_code.statics.len(), format!("%static{}", id.index())),
+            };
+            if !defined {
+                return Err(ParseError { line, column, message: format!("{} is never defined", name) });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Code {
+    /// Parses a module in the syntax described in `mir::text` into a fresh `Code`.
+    /// Function names are interned into `interner`.
+    pub fn parse_mir(src: &str, interner: &mut StringInterner) -> Result<Code, ParseError> {
+        let mut parser = Parser {
+            tokens: lex(src)?,
+            pos: 0,
+            code: Code::default(),
+            interner,
+            defined_ops: HashSet::new(),
+            defined_blocks: HashSet::new(),
+            references: Vec::new(),
+        };
+        loop {
+            if parser.is_keyword("string") {
+                parser.parse_string()?;
+            } else if parser.is_keyword("static") {
+                parser.parse_static()?;
+            } else if parser.is_keyword("fn") {
+                parser.parse_function()?;
+            } else if *parser.peek() == Token::Eof {
+                break;
+            } else {
+                return parser.error("expected `string`, `static` or `fn`");
+            }
+        }
+        parser.check_references()?;
+        Ok(parser.code)
+    }
+}
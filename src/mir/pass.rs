@@ -0,0 +1,254 @@
+//! A `Pass` trait for MIR transformations, and a `PassManager` to run them in order.
+
+use std::time::{Duration, Instant};
+
+use string_interner::StringInterner;
+
+use crate::arch::Arch;
+use crate::mir::fold::FoldError;
+use crate::mir::inline::InlineOptions;
+use crate::mir::verify::VerifyError;
+use crate::mir::FuncId;
+use crate::Code;
+
+/// A MIR transformation. Function-level passes implement `run_on_function`; whole-`Code` passes
+/// override `run` instead.
+pub trait Pass {
+    fn name(&self) -> &str;
+
+    /// Transforms `func`, returning the number of changes made
+    fn run_on_function(&mut self, _code: &mut Code, _func: FuncId) -> usize { 0 }
+
+    /// Transforms `code`, returning the number of changes made. Runs `run_on_function` on every
+    /// function by default.
+    fn run(&mut self, code: &mut Code) -> usize {
+        code.mir_code.functions.indices()
+            .map(|func| self.run_on_function(code, func))
+            .sum()
+    }
+}
+
+pub struct Mem2Reg;
+
+impl Pass for Mem2Reg {
+    fn name(&self) -> &str { "mem2reg" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.mem2reg(func)
+    }
+}
+
+pub struct FoldConstants {
+    pub arch: Arch,
+    /// Instrs that would trap, from every run of the pass
+    pub errors: Vec<FoldError>,
+}
+
+impl FoldConstants {
+    pub fn new(arch: Arch) -> Self {
+        FoldConstants { arch, errors: Vec::new() }
+    }
+}
+
+impl Pass for FoldConstants {
+    fn name(&self) -> &str { "fold" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        let (num_folded, errors) = code.fold_constants(func, self.arch);
+        self.errors.extend(errors);
+        num_folded
+    }
+}
+
+pub struct Sccp {
+    pub arch: Arch,
+}
+
+impl Pass for Sccp {
+    fn name(&self) -> &str { "sccp" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.sccp(func, self.arch)
+    }
+}
+
+pub struct Gvn;
+
+impl Pass for Gvn {
+    fn name(&self) -> &str { "gvn" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.gvn(func)
+    }
+}
+
+pub struct Licm;
+
+impl Pass for Licm {
+    fn name(&self) -> &str { "licm" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.licm(func)
+    }
+}
+
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &str { "dce" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.eliminate_dead_code(func)
+    }
+}
+
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &str { "simplify_cfg" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.simplify_cfg(func)
+    }
+}
+
+pub struct Inline {
+    pub options: InlineOptions,
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &str { "inline" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        code.inline_calls(func, &self.options)
+    }
+}
+
+/// Creates the built-in pass called `name`
+pub fn create_pass(name: &str, arch: Arch) -> Option<Box<dyn Pass>> {
+    let pass: Box<dyn Pass> = match name {
+        "mem2reg" => Box::new(Mem2Reg),
+        "fold" => Box::new(FoldConstants::new(arch)),
+        "sccp" => Box::new(Sccp { arch }),
+        "gvn" => Box::new(Gvn),
+        "licm" => Box::new(Licm),
+        "dce" => Box::new(DeadCodeElimination),
+        "simplify_cfg" => Box::new(SimplifyCfg),
+        "inline" => Box::new(Inline { options: InlineOptions::default() }),
+        _ => return None,
+    };
+    Some(pass)
+}
+
+/// Named sequences of built-in passes, for `PassManager::with_pipeline`
+pub const PIPELINES: &[(&str, &[&str])] = &[
+    ("none", &[]),
+    ("default", &["inline", "mem2reg", "fold", "sccp", "gvn", "licm", "simplify_cfg", "dce"]),
+];
+
+#[derive(Debug)]
+pub struct PassStats {
+    pub name: String,
+    pub duration: Duration,
+    pub changes: usize,
+}
+
+/// All MIR, as printed by `Code::display_mir`, before and after a run of a pass
+pub struct MirDump {
+    pub pass: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// The verifier rejected the MIR produced by a pass
+#[derive(Debug)]
+pub struct PassError {
+    pub pass: String,
+    pub errors: Vec<(FuncId, Vec<VerifyError>)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PipelineError {
+    /// There is no pipeline with this name in `PIPELINES`
+    UnknownPipeline(String),
+    /// The pipeline lists a pass that `create_pass` doesn't know
+    UnknownPass(String),
+}
+
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    verify_each: bool,
+    dump_around: Option<String>,
+    stats: Vec<PassStats>,
+    dumps: Vec<MirDump>,
+}
+
+impl PassManager {
+    pub fn new() -> Self { Self::default() }
+
+    /// Creates a pass manager running the pipeline called `name` from `PIPELINES`
+    pub fn with_pipeline(name: &str, arch: Arch) -> Result<Self, PipelineError> {
+        let &(_, passes) = PIPELINES.iter()
+            .find(|(pipeline, _)| *pipeline == name)
+            .ok_or_else(|| PipelineError::UnknownPipeline(name.to_string()))?;
+        let mut manager = PassManager::new();
+        for &pass in passes {
+            let pass = create_pass(pass, arch).ok_or_else(|| PipelineError::UnknownPass(pass.to_string()))?;
+            manager.passes.push(pass);
+        }
+        Ok(manager)
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    /// Runs the verifier on every function after each pass
+    pub fn set_verify_each(&mut self, verify_each: bool) {
+        self.verify_each = verify_each;
+    }
+
+    /// Records all MIR before and after each run of the pass called `name`, in `dumps`
+    pub fn set_dump_around(&mut self, name: Option<String>) {
+        self.dump_around = name;
+    }
+
+    /// Statistics for each pass run so far, in the order they ran
+    pub fn stats(&self) -> &[PassStats] { &self.stats }
+
+    /// The MIR recorded around each run of the pass set by `set_dump_around`, in the order they ran
+    pub fn dumps(&self) -> &[MirDump] { &self.dumps }
+
+    pub fn passes(&self) -> &[Box<dyn Pass>] { &self.passes }
+
+    /// Runs every pass in order, stopping at the first one whose output fails verification
+    pub fn run(&mut self, code: &mut Code, interner: &StringInterner) -> Result<(), PassError> {
+        for pass in &mut self.passes {
+            let name = pass.name().to_string();
+            let before = if self.dump_around.as_deref() == Some(name.as_str()) {
+                Some(code.display_mir(interner).to_string())
+            } else {
+                None
+            };
+            let start = Instant::now();
+            let changes = pass.run(code);
+            self.stats.push(PassStats { name: name.clone(), duration: start.elapsed(), changes });
+            if let Some(before) = before {
+                let after = code.display_mir(interner).to_string();
+                self.dumps.push(MirDump { pass: name.clone(), before, after });
+            }
+
+            if self.verify_each {
+                let errors: Vec<_> = code.mir_code.functions.iter_enumerated()
+                    .map(|(id, func)| (id, code.verify_function(func)))
+                    .filter(|(_, errors)| !errors.is_empty())
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(PassError { pass: name, errors });
+                }
+            }
+        }
+        Ok(())
+    }
+}
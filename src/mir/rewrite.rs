@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use index_vec::IndexVec;
+
+use crate::mir::{FuncId, Instr, VOID_INSTR};
+use crate::source_info::SourceRange;
+use crate::{Code, Op, Block, BlockId, OpId};
+
+#[derive(Debug)]
+pub enum RewriteError {
+    /// The op can't be erased, because it still has uses
+    HasUses(OpId),
+    /// The op is not in the given block
+    NotInBlock(OpId, BlockId),
+    /// The first block doesn't end in an unconditional branch to the second
+    NotBranchTo(BlockId),
+    /// The block can't be merged into its predecessor, because another block also branches to it
+    OtherPredecessors(BlockId),
+    /// The entry block can't be merged into another block
+    EntryBlock,
+}
+
+impl Code {
+    /// Rewrites every use of `old` to read `new` instead, returning the number of uses rewritten
+    pub fn replace_all_uses(&mut self, old: OpId, new: OpId) -> usize {
+        if old == new {
+            return 0;
+        }
+        let mut users = self.mir_code.uses.remove(&old).unwrap_or_default();
+        let num_uses = users.len();
+        users.sort();
+        users.dedup();
+        for &user in &users {
+            if let Op::MirInstr(instr) = &mut self.ops[user] {
+                for operand in instr.operands_mut() {
+                    if *operand == old {
+                        *operand = new;
+                        self.mir_code.uses.entry(new).or_default().push(user);
+                    }
+                }
+            }
+        }
+        num_uses
+    }
+
+    /// Removes `op` from `block` and replaces it in the arena with a `Void` instr. `op` must not
+    /// have any uses. The slot in the arena is reclaimed by `compact_ops`.
+    pub fn erase_op(&mut self, block: BlockId, op: OpId) -> Result<(), RewriteError> {
+        if self.has_uses(op) {
+            return Err(RewriteError::HasUses(op));
+        }
+        let index = self.position_in_block(block, op)?;
+        let was_terminator = self.ops[op].as_mir_instr().map(|instr| instr.is_terminator()).unwrap_or(false);
+        self.blocks[block].ops.remove(index);
+        self.set_mir_instr(op, Instr::Void);
+        self.mir_code.source_ranges.remove(&op);
+        self.mir_code.instr_names.remove(&op);
+        if was_terminator {
+            self.mir_code.reopen_block(block);
+        }
+        Ok(())
+    }
+
+    /// Moves `op` out of `from` and inserts it into `to` at `index`
+    pub fn move_op(&mut self, op: OpId, from: BlockId, to: BlockId, index: usize) -> Result<(), RewriteError> {
+        let old_index = self.position_in_block(from, op)?;
+        self.blocks[from].ops.remove(old_index);
+        self.blocks[to].ops.insert(index, op);
+        let is_terminator = self.ops[op].as_mir_instr().map(|instr| instr.is_terminator()).unwrap_or(false);
+        if is_terminator {
+            self.mir_code.reopen_block(from);
+            if index + 1 == self.blocks[to].ops.len() {
+                self.mir_code.reopen_block(to);
+                self.mir_code.end_block(to).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the ops of `block` from `index` onwards into a new block, which is inserted into `func`
+    /// right after `block`. `block` then ends with a branch to the new block, which is returned.
+    pub fn split_block(&mut self, func: FuncId, block: BlockId, index: usize) -> BlockId {
+        let ops = self.blocks[block].ops.split_off(index);
+        let new_block = self.blocks.push(Block { ops });
+        let blocks = &mut self.mir_code.functions[func].blocks;
+        let position = blocks.iter().position(|&b| b == block).unwrap();
+        blocks.insert(position + 1, new_block);
+
+        self.mir_code.start_block(new_block).unwrap();
+        if self.mir_code.is_block_ended(block) {
+            self.mir_code.end_block(new_block).unwrap();
+            self.mir_code.reopen_block(block);
+        }
+        let br = self.push_mir_instr(Instr::Br(new_block));
+        self.blocks[block].ops.push(br);
+        self.mir_code.source_ranges.insert(br, SourceRange::default());
+        self.mir_code.end_block(block).unwrap();
+        self.retarget_phis(new_block, block, new_block);
+        new_block
+    }
+
+    /// Appends the ops of `b` to `a` and removes `b` from `func`. `a` must end in an unconditional
+    /// branch to `b`, which is erased, and no other block in `func` may branch to `b`.
+    pub fn merge_blocks(&mut self, func: FuncId, a: BlockId, b: BlockId) -> Result<(), RewriteError> {
+        let function = &self.mir_code.functions[func];
+        if function.blocks[0] == b {
+            return Err(RewriteError::EntryBlock);
+        }
+        let br = match self.blocks[a].ops.last() {
+            Some(&br) if matches!(self.ops[br].as_mir_instr(), Some(&Instr::Br(target)) if target == b) => br,
+            _ => return Err(RewriteError::NotBranchTo(b)),
+        };
+        for &block in &function.blocks {
+            if block == a {
+                continue;
+            }
+            if let Some(terminator) = self.terminator(block) {
+                if terminator.successors().contains(&b) {
+                    return Err(RewriteError::OtherPredecessors(b));
+                }
+            }
+        }
+
+        // `b` has `a` as its only predecessor, so each of its phis has a single incoming value
+        let phis: Vec<OpId> = self.blocks[b].ops.iter()
+            .copied()
+            .take_while(|&op| matches!(self.ops[op].as_mir_instr(), Some(Instr::Phi { .. })))
+            .collect();
+        for phi in phis {
+            let value = match self.ops[phi].as_mir_instr() {
+                Some(Instr::Phi { incoming }) => incoming.first().map(|edge| edge.value).unwrap_or(VOID_INSTR),
+                _ => unreachable!(),
+            };
+            self.replace_all_uses(phi, value);
+            self.erase_op(b, phi)?;
+        }
+        self.retarget_phis(b, b, a);
+
+        self.erase_op(a, br)?;
+        let ops = std::mem::take(&mut self.blocks[b].ops);
+        self.blocks[a].ops.extend(ops);
+        if self.mir_code.is_block_ended(b) {
+            self.mir_code.end_block(a).unwrap();
+        }
+        self.mir_code.functions[func].blocks.retain(|&block| block != b);
+        Ok(())
+    }
+
+    /// Removes every MIR instr that is not in a block, and renumbers the remaining ops to fill the
+    /// gaps. Source ranges, instr names and use lists are rewritten to match. Returns the new id of
+    /// each old op, or `None` if it was removed.
+    ///
+    /// Operands that refer to a removed op are rewritten to `VOID_INSTR`.
+    pub fn compact_ops(&mut self) -> IndexVec<OpId, Option<OpId>> {
+        let mut live: IndexVec<OpId, bool> = self.ops.iter()
+            .map(|op| matches!(op, Op::HirItem(_)))
+            .collect();
+        live[VOID_INSTR] = true;
+        for block in &self.blocks {
+            for &op in &block.ops {
+                live[op] = true;
+            }
+        }
+
+        let old_ops = std::mem::take(&mut self.ops);
+        let mut remap = IndexVec::with_capacity(old_ops.len());
+        for (id, op) in old_ops.into_iter_enumerated() {
+            if live[id] {
+                remap.push(Some(self.ops.push(op)));
+            } else {
+                remap.push(None);
+            }
+        }
+
+        let new_id = |op: OpId| remap[op].unwrap_or(VOID_INSTR);
+        for op in &mut self.ops {
+            if let Op::MirInstr(instr) = op {
+                for operand in instr.operands_mut() {
+                    *operand = new_id(*operand);
+                }
+            }
+        }
+        for block in &mut self.blocks {
+            for op in &mut block.ops {
+                *op = new_id(*op);
+            }
+        }
+        self.mir_code.source_ranges = self.mir_code.source_ranges.drain()
+            .filter_map(|(op, range)| remap[op].map(|op| (op, range)))
+            .collect::<HashMap<_, _>>();
+        self.mir_code.instr_names = self.mir_code.instr_names.drain()
+            .filter_map(|(op, name)| remap[op].map(|op| (op, name)))
+            .collect::<HashMap<_, _>>();
+        self.rebuild_uses();
+        remap
+    }
+
+    /// In the phis of each successor of `block`, rewrites incoming edges from `old_pred` to come
+    /// from `new_pred` instead
+    pub fn retarget_phis(&mut self, block: BlockId, old_pred: BlockId, new_pred: BlockId) {
+        let successors = match self.terminator(block) {
+            Some(terminator) => terminator.successors(),
+            None => return,
+        };
+        for successor in successors {
+            for &op in &self.blocks[successor].ops {
+                match &mut self.ops[op] {
+                    Op::MirInstr(Instr::Phi { incoming }) => for edge in incoming {
+                        if edge.bb == old_pred {
+                            edge.bb = new_pred;
+                        }
+                    },
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    /// Removes incoming edges from `pred` in the phis of `block`
+    pub fn remove_phi_edges(&mut self, block: BlockId, pred: BlockId) {
+        for i in 0..self.blocks[block].ops.len() {
+            let op = self.blocks[block].ops[i];
+            let mut incoming = match self.ops[op].as_mir_instr() {
+                Some(Instr::Phi { incoming }) => incoming.clone(),
+                _ => break,
+            };
+            incoming.retain(|edge| edge.bb != pred);
+            self.set_mir_instr(op, Instr::Phi { incoming });
+        }
+    }
+
+    fn position_in_block(&self, block: BlockId, op: OpId) -> Result<usize, RewriteError> {
+        self.blocks[block].ops.iter()
+            .position(|&other| other == op)
+            .ok_or(RewriteError::NotInBlock(op, block))
+    }
+}
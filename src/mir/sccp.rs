@@ -0,0 +1,285 @@
+//! Sparse conditional constant propagation, after Wegman and Zadeck. Values are only propagated
+//! along control flow edges that can actually be taken, so a constant branch condition also keeps
+//! the code it skips from making other values non-constant.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::arch::Arch;
+use crate::hir::Intrinsic;
+use crate::mir::cfg::Cfg;
+use crate::mir::eval::{self, CastKind};
+use crate::mir::simplify_cfg::switch_case_taken;
+use crate::mir::{Const, FuncId, Instr};
+use crate::ty::Type;
+use crate::{Code, BlockId, OpId};
+
+#[derive(Clone, Debug, PartialEq)]
+enum LatticeValue {
+    /// Nothing is known yet, because the op hasn't been reached
+    Undefined,
+    Constant(Const),
+    /// The op may have more than one value
+    Overdefined,
+}
+
+impl LatticeValue {
+    fn meet(&self, other: &LatticeValue) -> LatticeValue {
+        match (self, other) {
+            (LatticeValue::Undefined, value) | (value, LatticeValue::Undefined) => value.clone(),
+            (LatticeValue::Constant(a), LatticeValue::Constant(b)) if a == b => self.clone(),
+            _ => LatticeValue::Overdefined,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SccpState {
+    values: HashMap<OpId, LatticeValue>,
+    executable_edges: HashSet<(BlockId, BlockId)>,
+    executable_blocks: HashSet<BlockId>,
+    edge_worklist: Vec<(BlockId, BlockId)>,
+    op_worklist: Vec<OpId>,
+    /// The block containing each op in the function
+    op_blocks: HashMap<OpId, BlockId>,
+}
+
+impl SccpState {
+    fn value(&self, op: OpId) -> LatticeValue {
+        self.values.get(&op).cloned().unwrap_or(LatticeValue::Undefined)
+    }
+
+    fn is_executable(&self, from: BlockId, to: BlockId) -> bool {
+        self.executable_edges.contains(&(from, to))
+    }
+}
+
+impl Code {
+    /// Evaluates `op`, in `block`, given what is known so far about its operands
+    fn evaluate_lattice(&self, state: &SccpState, block: BlockId, op: OpId, arch: Arch) -> LatticeValue {
+        let instr = match self.ops[op].as_mir_instr() {
+            Some(instr) => instr,
+            None => return LatticeValue::Overdefined,
+        };
+        if let Some((kind, operand, ty)) = CastKind::of(instr) {
+            return match state.value(operand) {
+                LatticeValue::Constant(val) => eval::eval_cast(kind, &val, ty, arch)
+                    .map(LatticeValue::Constant)
+                    .unwrap_or(LatticeValue::Overdefined),
+                value => value,
+            };
+        }
+        let (intr, arguments, ty) = match instr {
+            Instr::Const(konst) => return LatticeValue::Constant(konst.clone()),
+            &Instr::Struct { id, .. } => return LatticeValue::Constant(Const::Ty(Type::Struct(id))),
+            &Instr::Enum { id, .. } => return LatticeValue::Constant(Const::Ty(Type::Enum(id))),
+            &Instr::Pointer { op, is_mut } => return match state.value(op) {
+                LatticeValue::Constant(Const::Ty(pointee)) => LatticeValue::Constant(Const::Ty(self.types.ptr_with_mut(pointee, is_mut))),
+                LatticeValue::Constant(_) => LatticeValue::Overdefined,
+                value => value,
+            },
+            Instr::Phi { incoming } => return incoming.iter()
+                .filter(|edge| state.is_executable(edge.bb, block))
+                .fold(LatticeValue::Undefined, |value, edge| value.meet(&state.value(edge.value))),
+            &Instr::LogicalNot(operand) => (Intrinsic::LogicalNot, vec![operand], Type::Bool),
+            Instr::Intrinsic { intr, arguments, ty } if !instr.has_side_effects() => (*intr, arguments.to_vec(), *ty),
+            _ => return LatticeValue::Overdefined,
+        };
+        let values: Vec<LatticeValue> = arguments.iter().map(|&argument| state.value(argument)).collect();
+        if values.contains(&LatticeValue::Overdefined) {
+            return LatticeValue::Overdefined;
+        }
+        let mut konsts = Vec::new();
+        for value in values {
+            match value {
+                LatticeValue::Constant(konst) => konsts.push(konst),
+                _ => return LatticeValue::Undefined,
+            }
+        }
+        self.eval_intrinsic(intr, &konsts, &ty, arch)
+            .map(LatticeValue::Constant)
+            .unwrap_or(LatticeValue::Overdefined)
+    }
+
+    /// Marks the edges out of `block` that its terminator can take, given what is known so far
+    fn mark_successors(&self, state: &mut SccpState, block: BlockId) {
+        let terminator = match self.terminator(block) {
+            Some(terminator) => terminator,
+            None => return,
+        };
+        let targets = match *terminator {
+            Instr::CondBr { condition, true_bb, false_bb } => match state.value(condition) {
+                LatticeValue::Undefined => vec![],
+                LatticeValue::Constant(Const::Bool(true)) => vec![true_bb],
+                LatticeValue::Constant(Const::Bool(false)) => vec![false_bb],
+                _ => vec![true_bb, false_bb],
+            },
+            Instr::SwitchBr { scrutinee, ref cases, catch_all_bb } => match state.value(scrutinee) {
+                LatticeValue::Undefined => vec![],
+                LatticeValue::Constant(scrutinee) => {
+                    let mut targets = vec![catch_all_bb];
+                    for case in cases {
+                        match switch_case_taken(&scrutinee, &case.value) {
+                            Some(true) => {
+                                targets = vec![case.bb];
+                                break;
+                            },
+                            Some(false) => {},
+                            None => {
+                                targets = terminator.successors().to_vec();
+                                break;
+                            },
+                        }
+                    }
+                    targets
+                },
+                LatticeValue::Overdefined => terminator.successors().to_vec(),
+            },
+            _ => terminator.successors().to_vec(),
+        };
+        for target in targets {
+            if !state.is_executable(block, target) {
+                state.edge_worklist.push((block, target));
+            }
+        }
+    }
+
+    fn visit_op(&self, state: &mut SccpState, block: BlockId, op: OpId, arch: Arch) {
+        if self.ops[op].as_mir_instr().map(|instr| instr.is_terminator()).unwrap_or(false) {
+            self.mark_successors(state, block);
+            return;
+        }
+        let old_value = state.value(op);
+        let new_value = old_value.meet(&self.evaluate_lattice(state, block, op, arch));
+        if new_value != old_value {
+            state.values.insert(op, new_value);
+            state.op_worklist.extend_from_slice(self.uses(op));
+        }
+    }
+
+    /// Runs SCCP to a fixed point, returning the value of every op and the executable blocks
+    fn analyze_sccp(&self, func: FuncId, arch: Arch) -> SccpState {
+        let function = &self.mir_code.functions[func];
+        let mut state = SccpState::default();
+        for &block in &function.blocks {
+            for &op in &self.blocks[block].ops {
+                state.op_blocks.insert(op, block);
+            }
+        }
+
+        let entry = function.blocks[0];
+        state.executable_blocks.insert(entry);
+        for &op in &self.blocks[entry].ops {
+            self.visit_op(&mut state, entry, op, arch);
+        }
+        loop {
+            if let Some((from, to)) = state.edge_worklist.pop() {
+                if !state.executable_edges.insert((from, to)) {
+                    continue;
+                }
+                // The first time a block is reached all of its ops are evaluated; after that only
+                // its phis can change
+                let first_visit = state.executable_blocks.insert(to);
+                for &op in &self.blocks[to].ops {
+                    let is_phi = self.ops[op].as_mir_instr().map(|instr| instr.is_phi()).unwrap_or(false);
+                    if !first_visit && !is_phi {
+                        break;
+                    }
+                    self.visit_op(&mut state, to, op, arch);
+                }
+            } else if let Some(op) = state.op_worklist.pop() {
+                // Uses in other functions, or in blocks not reached yet, are skipped
+                if let Some(&block) = state.op_blocks.get(&op) {
+                    if state.executable_blocks.contains(&block) {
+                        self.visit_op(&mut state, block, op, arch);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        state
+    }
+
+    /// Propagates constants through `func`, following only the branches that can be taken. Instrs
+    /// found to be constant become `Const`s, indirect calls through a constant function pointer
+    /// become direct calls, branches that can only go one way become `Br`s, and blocks that can't
+    /// be reached are removed. Returns the number of instrs replaced, branches simplified, and
+    /// blocks and ops removed.
+    pub fn sccp(&mut self, func: FuncId, arch: Arch) -> usize {
+        let function = &self.mir_code.functions[func];
+        if function.blocks.is_empty() {
+            return 0;
+        }
+        let state = self.analyze_sccp(func, arch);
+        let cfg = Cfg::new(self, function);
+        let blocks: Vec<BlockId> = function.blocks.iter()
+            .copied()
+            .filter(|block| state.executable_blocks.contains(block))
+            .collect();
+
+        let mut num_changes = 0;
+        for &block in &blocks {
+            let num_phis = self.blocks[block].ops.iter()
+                .take_while(|&&op| self.ops[op].as_mir_instr().map(|instr| instr.is_phi()).unwrap_or(false))
+                .count();
+            for i in 0..self.blocks[block].ops.len() {
+                let op = self.blocks[block].ops[i];
+                if let Some(Instr::CallIndirect { arguments, callee }) = self.ops[op].as_mir_instr() {
+                    if let LatticeValue::Constant(Const::Func { id, ty }) = state.value(*callee) {
+                        // A direct call would need generic arguments, and a mistyped pointer must
+                        // stay an error at the call instead of calling the function anyway
+                        let callee_function = &self.mir_code.functions[id];
+                        let params_match = self.types.signature(ty)
+                            .map(|(params, _)| params.len() == arguments.len())
+                            .unwrap_or(false);
+                        if callee_function.generic_params.is_empty() && ty == self.func_ptr_ty(callee_function) && params_match {
+                            let call = Instr::Call { arguments: arguments.clone(), generic_arguments: Vec::new(), func: id };
+                            self.set_mir_instr(op, call);
+                            num_changes += 1;
+                        }
+                    }
+                    continue;
+                }
+                let konst = match (self.ops[op].as_mir_instr(), state.value(op)) {
+                    // `Struct` and `Enum` instrs declare the fields of their types, so they stay
+                    (Some(Instr::Const(_)), _) | (Some(Instr::Struct { .. }), _) | (Some(Instr::Enum { .. }), _) => continue,
+                    (Some(instr), LatticeValue::Constant(konst)) if instr.is_pure() => konst,
+                    _ => continue,
+                };
+                self.set_mir_instr(op, Instr::Const(konst));
+                num_changes += 1;
+            }
+
+            // Phis can't become `Const`s in place, since they must stay at the start of the block
+            let mut i = 0;
+            for _ in 0..num_phis {
+                let op = self.blocks[block].ops[i];
+                match state.value(op) {
+                    LatticeValue::Constant(konst) => {
+                        let new_op = self.push_mir_instr(Instr::Const(konst));
+                        self.blocks[block].ops.insert(num_phis, new_op);
+                        if let Some(&range) = self.mir_code.source_ranges.get(&op) {
+                            self.mir_code.source_ranges.insert(new_op, range);
+                        }
+                        self.replace_all_uses(op, new_op);
+                        self.erase_op(block, op).unwrap();
+                        num_changes += 1;
+                    },
+                    _ => i += 1,
+                }
+            }
+
+            let targets: Vec<BlockId> = cfg.successors(block).iter()
+                .copied()
+                .filter(|&successor| state.is_executable(block, successor))
+                .collect();
+            if let &[target] = targets.as_slice() {
+                if !matches!(self.terminator(block), Some(Instr::Br(_))) {
+                    self.replace_with_br(&cfg, block, target);
+                    num_changes += 1;
+                }
+            }
+        }
+        num_changes + self.remove_unreachable_blocks(func)
+    }
+}
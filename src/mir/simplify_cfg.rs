@@ -0,0 +1,182 @@
+//! Simplification of MIR control flow: folding branches on constants, threading jumps through
+//! empty blocks and merging straight-line chains of blocks.
+
+use std::collections::HashSet;
+
+use crate::mir::cfg::Cfg;
+use crate::mir::{Const, FuncId, Instr, PhiEdge};
+use crate::{Code, BlockId, OpId};
+
+/// Whether a `SwitchBr` on `scrutinee` takes the case for `case`, or `None` if that isn't known
+pub(crate) fn switch_case_taken(scrutinee: &Const, case: &Const) -> Option<bool> {
+    match (scrutinee, case) {
+        (&Const::BasicVariant { enuum, index }, &Const::BasicVariant { enuum: case_enuum, index: case_index }) if enuum == case_enuum
+            => Some(index == case_index),
+        (Const::Int { lit, .. }, Const::Int { lit: case_lit, .. }) => Some(lit == case_lit),
+        (Const::Bool(val), Const::Bool(case_val)) => Some(val == case_val),
+        _ => None,
+    }
+}
+
+impl Code {
+    fn const_value(&self, op: OpId) -> Option<&Const> {
+        match self.ops[op].as_mir_instr() {
+            Some(Instr::Const(konst)) => Some(konst),
+            _ => None,
+        }
+    }
+
+    /// If the terminator of `block` always goes to the same successor, returns that successor
+    fn constant_target(&self, block: BlockId) -> Option<BlockId> {
+        match *self.terminator(block)? {
+            Instr::CondBr { true_bb, false_bb, .. } if true_bb == false_bb => Some(true_bb),
+            Instr::CondBr { condition, true_bb, false_bb } => match self.const_value(condition)? {
+                Const::Bool(true) => Some(true_bb),
+                Const::Bool(false) => Some(false_bb),
+                _ => None,
+            },
+            Instr::SwitchBr { scrutinee, ref cases, catch_all_bb } => {
+                if cases.iter().all(|case| case.bb == catch_all_bb) {
+                    return Some(catch_all_bb);
+                }
+                let scrutinee = self.const_value(scrutinee)?;
+                for case in cases {
+                    if switch_case_taken(scrutinee, &case.value)? {
+                        return Some(case.bb);
+                    }
+                }
+                Some(catch_all_bb)
+            },
+            _ => None,
+        }
+    }
+
+    /// Replaces the terminator of `block` with a `Br` to `target`, removing the phi edges from
+    /// `block` in the successors it no longer branches to
+    pub(crate) fn replace_with_br(&mut self, cfg: &Cfg, block: BlockId, target: BlockId) {
+        let terminator = *self.blocks[block].ops.last().unwrap();
+        self.set_mir_instr(terminator, Instr::Br(target));
+        for &successor in cfg.successors(block) {
+            if successor != target {
+                self.remove_phi_edges(successor, block);
+            }
+        }
+    }
+
+    /// If `block` contains nothing but a `Br`, returns its target
+    fn empty_br_target(&self, block: BlockId) -> Option<BlockId> {
+        match self.blocks[block].ops.as_slice() {
+            &[op] => match *self.ops[op].as_mir_instr()? {
+                Instr::Br(target) if target != block => Some(target),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether following `Br`s from `block` through empty blocks leads back to `block`. Threading
+    /// jumps through such a cycle would never terminate.
+    fn in_empty_cycle(&self, block: BlockId) -> bool {
+        let mut visited = HashSet::new();
+        let mut current = block;
+        while let Some(target) = self.empty_br_target(current) {
+            if target == block {
+                return true;
+            }
+            if !visited.insert(target) {
+                return false;
+            }
+            current = target;
+        }
+        false
+    }
+
+    /// Redirects `pred`'s edges to the empty block `block` so that they go straight to `target`,
+    /// if the phis of `target` allow it. Returns whether anything changed.
+    fn thread_jump(&mut self, cfg: &Cfg, pred: BlockId, block: BlockId, target: BlockId) -> bool {
+        let has_phis = self.blocks[target].ops.first()
+            .and_then(|&op| self.ops[op].as_mir_instr())
+            .map(|instr| instr.is_phi())
+            .unwrap_or(false);
+        // A phi can't distinguish two edges from the same block
+        if has_phis && cfg.successors(pred).contains(&target) {
+            return false;
+        }
+        let terminator = *self.blocks[pred].ops.last().unwrap();
+        let mut instr = self.ops[terminator].as_mir_instr().unwrap().clone();
+        for successor in instr.successors_mut() {
+            if *successor == block {
+                *successor = target;
+            }
+        }
+        self.set_mir_instr(terminator, instr);
+
+        // Values that flowed into `target` from `block` now also flow in from `pred`
+        for i in 0..self.blocks[target].ops.len() {
+            let op = self.blocks[target].ops[i];
+            let mut incoming = match self.ops[op].as_mir_instr() {
+                Some(Instr::Phi { incoming }) => incoming.clone(),
+                _ => break,
+            };
+            if let Some(value) = incoming.iter().find(|edge| edge.bb == block).map(|edge| edge.value) {
+                incoming.push(PhiEdge { bb: pred, value });
+            }
+            self.set_mir_instr(op, Instr::Phi { incoming });
+        }
+        true
+    }
+
+    /// Makes a single simplification to `func`, returning whether anything changed
+    fn simplify_cfg_step(&mut self, func: FuncId) -> bool {
+        let function = &self.mir_code.functions[func];
+        let cfg = Cfg::new(self, function);
+        let entry = cfg.entry();
+        for &block in cfg.reverse_postorder() {
+            if !matches!(self.terminator(block), Some(Instr::Br(_))) {
+                if let Some(target) = self.constant_target(block) {
+                    self.replace_with_br(&cfg, block, target);
+                    return true;
+                }
+            }
+
+            if block != entry && !self.in_empty_cycle(block) {
+                if let Some(target) = self.empty_br_target(block) {
+                    let mut changed = false;
+                    for &pred in cfg.predecessors(block) {
+                        changed |= self.thread_jump(&cfg, pred, block, target);
+                    }
+                    if changed {
+                        return true;
+                    }
+                }
+            }
+
+            // `merge_blocks` checks the rest of what makes a merge legal before changing anything
+            if let &[pred] = cfg.predecessors(block) {
+                if pred != block && self.merge_blocks(func, pred, block).is_ok() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Simplifies the control flow of `func` until nothing more can be done:
+    ///   - `CondBr`s on constant bools, and `SwitchBr`s on constant scrutinees or whose cases all
+    ///     go to the same block, become `Br`s
+    ///   - branches to blocks containing only a `Br` go straight to that `Br`'s target
+    ///   - blocks are merged into their only predecessor when it has no other successors
+    ///
+    /// Blocks left unreachable are not removed; `eliminate_dead_code` takes care of those.
+    /// Returns the number of simplifications made.
+    pub fn simplify_cfg(&mut self, func: FuncId) -> usize {
+        if self.mir_code.functions[func].blocks.is_empty() {
+            return 0;
+        }
+        let mut num_changes = 0;
+        while self.simplify_cfg_step(func) {
+            num_changes += 1;
+        }
+        num_changes
+    }
+}
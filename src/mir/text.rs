@@ -0,0 +1,388 @@
+//! The textual form of MIR, as printed by `Code::display_func`, `Code::display_block` and
+//! `Code::display_mir`, and read back by `Code::parse_mir`.
+//!
+//! ```text
+//! module      := item*
+//! item        := string | static | function
+//! string      := "string" %strN "=" STRING
+//! static      := "static" %staticN STRING "=" const
+//! function    := "fn" ("@f" N)? STRING? generics? "->" type ("decl" %declN)? "{" block* "}"
+//! generics    := "<" generic_paramN ("," generic_paramN)* ">"
+//! block       := %bbN ":" op*
+//! op          := %opN STRING? "=" "mir." instr ("@" N ".." N)?
+//!
+//! instr       := "void"
+//!              | "const" const
+//!              | "alloca" type
+//!              | "logical_not" %opN
+//!              | "call" @fN ("<" type ("," type)* ">")? "(" operands ")"
+//!              | "call_indirect" %opN "(" operands ")"
+//!              | "intrinsic" INTRINSIC type "(" operands ")"
+//!              | CAST %opN "to" type
+//!              | "load" %opN
+//!              | "store" %opN "to" %opN
+//!              | "address_of_static" %staticN
+//!              | "pointer" "mut"? %opN
+//!              | ("struct" | "struct_lit") structN "(" operands ")"
+//!              | "enum" enumN "(" operands ")"
+//!              | ("direct_field_access" | "indirect_field_access") %opN "," N
+//!              | "variant" enumN "," N "," %opN
+//!              | "discriminant_access" %opN
+//!              | ("element_access" | "element_address") %opN "," %opN
+//!              | "make_slice" %opN "," %opN
+//!              | "slice_len" %opN
+//!              | "ret" %opN
+//!              | "br" %bbN
+//!              | "cond_br" %opN "," %bbN "," %bbN
+//!              | "switch_br" %opN "[" (const "=>" %bbN ("," const "=>" %bbN)*)? "]" "else" %bbN
+//!              | "generic_param" generic_paramN
+//!              | "parameter" type
+//!              | "phi" "[" (%bbN ":" %opN ("," %bbN ":" %opN)*)? "]"
+//! CAST        := "reinterpret" | "truncate" | "sign_extend" | "zero_extend"
+//!              | "float_cast" | "float_to_int" | "int_to_float"
+//! INTRINSIC   := the `Debug` name of a `hir::Intrinsic`, e.g. `Add` or `SizeOf`
+//! operands    := (%opN ("," %opN)*)?
+//!
+//! const       := "int" type N
+//!              | "float" type "-"? (FLOAT | "inf" | "NaN")
+//!              | "str" type %strN
+//!              | "func" type @fN
+//!              | "bool" ("true" | "false")
+//!              | "type" type
+//!              | "mod" %modN
+//!              | "variant" enumN N
+//!              | "struct" structN "{" (const ("," const)*)? "}"
+//!              | "array" type "[" (const ("," const)*)? "]"
+//!
+//! type        := "error" | "never" | "bool" | "void" | "module" | "type"
+//!              | "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+//!              | "f32" | "f64"
+//!              | "*" "mut"? type
+//!              | "[" type ";" N "]"
+//!              | "[" "]" "mut"? type
+//!              | "fn" "(" (type ("," type)*)? ")" "->" type
+//!              | structN | enumN | generic_paramN
+//! ```
+//!
+//! `N` is a decimal integer. Strings use C-style escapes (`\\`, `\"`, `\n`, `\t`, `\r` and
+//! `\xNN`). Everything from `//` to the end of a line is a comment. The number after each
+//! sigil is the index of the entity it names, and is preserved by the parser.
+
+use std::ffi::CStr;
+use std::fmt::{self, Formatter};
+
+use display_adapter::display_adapter;
+use string_interner::StringInterner;
+
+use crate::mir::{Const, Function, Instr};
+use crate::ty::{Type, IntWidth, FloatWidth};
+use crate::Code;
+
+pub(crate) fn write_str_lit(w: &mut Formatter, bytes: &[u8]) -> fmt::Result {
+    write!(w, "\"")?;
+    for &byte in bytes {
+        match byte {
+            b'\\' => write!(w, "\\\\")?,
+            b'"' => write!(w, "\\\"")?,
+            b'\n' => write!(w, "\\n")?,
+            b'\t' => write!(w, "\\t")?,
+            b'\r' => write!(w, "\\r")?,
+            0x20..=0x7E => write!(w, "{}", byte as char)?,
+            _ => write!(w, "\\x{:02x}", byte)?,
+        }
+    }
+    write!(w, "\"")
+}
+
+fn write_operands(w: &mut Formatter, operands: &[crate::OpId]) -> fmt::Result {
+    write!(w, "(")?;
+    for (i, op) in operands.iter().enumerate() {
+        if i > 0 {
+            write!(w, ", ")?;
+        }
+        write!(w, "%op{}", op.index())?;
+    }
+    write!(w, ")")
+}
+
+impl Code {
+    pub(crate) fn write_ty(&self, w: &mut Formatter, ty: &Type) -> fmt::Result {
+        match ty {
+            Type::Error => write!(w, "error"),
+            Type::Never => write!(w, "never"),
+            Type::Bool => write!(w, "bool"),
+            Type::Void => write!(w, "void"),
+            Type::Mod => write!(w, "module"),
+            Type::Ty => write!(w, "type"),
+            &Type::Int { width, is_signed } => write!(
+                w,
+                "{}{}",
+                if is_signed { 'i' } else { 'u' },
+                match width {
+                    IntWidth::W8 => "8",
+                    IntWidth::W16 => "16",
+                    IntWidth::W32 => "32",
+                    IntWidth::W64 => "64",
+                    IntWidth::Pointer => "size",
+                }
+            ),
+            Type::Float(FloatWidth::W32) => write!(w, "f32"),
+            Type::Float(FloatWidth::W64) => write!(w, "f64"),
+            &Type::Pointer { pointee, is_mut } => {
+                write!(w, "*")?;
+                if is_mut {
+                    write!(w, "mut ")?;
+                }
+                self.write_ty(w, &self.types.get(pointee))
+            },
+            &Type::Array { elem, len } => {
+                write!(w, "[")?;
+                self.write_ty(w, &self.types.get(elem))?;
+                write!(w, "; {}]", len)
+            },
+            &Type::Slice { elem, is_mut } => {
+                write!(w, "[]")?;
+                if is_mut {
+                    write!(w, "mut ")?;
+                }
+                self.write_ty(w, &self.types.get(elem))
+            },
+            &Type::Function { params, ret } => {
+                write!(w, "fn(")?;
+                for (i, param) in self.types.get_list(params).iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    self.write_ty(w, param)?;
+                }
+                write!(w, ") -> ")?;
+                self.write_ty(w, &self.types.get(ret))
+            },
+            Type::Struct(id) => write!(w, "struct{}", id.index()),
+            Type::Enum(id) => write!(w, "enum{}", id.index()),
+            Type::GenericParam(id) => write!(w, "generic_param{}", id.index()),
+        }
+    }
+
+    pub(crate) fn write_const(&self, w: &mut Formatter, konst: &Const) -> fmt::Result {
+        match konst {
+            Const::Int { lit, ty } => {
+                write!(w, "int ")?;
+                self.write_ty(w, ty)?;
+                write!(w, " {}", lit)
+            },
+            Const::Float { lit, ty } => {
+                write!(w, "float ")?;
+                self.write_ty(w, ty)?;
+                write!(w, " {:?}", lit)
+            },
+            Const::Str { id, ty } => {
+                write!(w, "str ")?;
+                self.write_ty(w, ty)?;
+                write!(w, " %str{}", id.index())
+            },
+            Const::Func { id, ty } => {
+                write!(w, "func ")?;
+                self.write_ty(w, ty)?;
+                write!(w, " @f{}", id.index())
+            },
+            Const::Bool(val) => write!(w, "bool {}", val),
+            Const::Ty(ty) => {
+                write!(w, "type ")?;
+                self.write_ty(w, ty)
+            },
+            Const::Mod(id) => write!(w, "mod %mod{}", id.index()),
+            Const::BasicVariant { enuum, index } => write!(w, "variant enum{} {}", enuum.index(), index),
+            Const::StructLit { fields, id } => {
+                write!(w, "struct struct{} {{", id.index())?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    self.write_const(w, field)?;
+                }
+                write!(w, "}}")
+            },
+            Const::Array { elems, ty } => {
+                write!(w, "array ")?;
+                self.write_ty(w, ty)?;
+                write!(w, " [")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    self.write_const(w, elem)?;
+                }
+                write!(w, "]")
+            },
+        }
+    }
+
+    pub(crate) fn write_instr(&self, w: &mut Formatter, instr: &Instr) -> fmt::Result {
+        let cast = |w: &mut Formatter, name: &str, op: crate::OpId, ty: &Type| -> fmt::Result {
+            write!(w, "{} %op{} to ", name, op.index())?;
+            self.write_ty(w, ty)
+        };
+        match instr {
+            Instr::Void => write!(w, "void"),
+            Instr::Const(konst) => {
+                write!(w, "const ")?;
+                self.write_const(w, konst)
+            },
+            Instr::Alloca(ty) => {
+                write!(w, "alloca ")?;
+                self.write_ty(w, ty)
+            },
+            Instr::LogicalNot(op) => write!(w, "logical_not %op{}", op.index()),
+            Instr::Call { arguments, generic_arguments, func } => {
+                write!(w, "call @f{}", func.index())?;
+                if !generic_arguments.is_empty() {
+                    write!(w, "<")?;
+                    for (i, ty) in generic_arguments.iter().enumerate() {
+                        if i > 0 {
+                            write!(w, ", ")?;
+                        }
+                        self.write_ty(w, ty)?;
+                    }
+                    write!(w, ">")?;
+                }
+                write_operands(w, arguments)
+            },
+            Instr::CallIndirect { arguments, callee } => {
+                write!(w, "call_indirect %op{}", callee.index())?;
+                write_operands(w, arguments)
+            },
+            Instr::Intrinsic { arguments, ty, intr } => {
+                write!(w, "intrinsic {} ", intr.mir_name())?;
+                self.write_ty(w, ty)?;
+                write!(w, " ")?;
+                write_operands(w, arguments)
+            },
+            &Instr::Reinterpret(op, ref ty) => cast(w, "reinterpret", op, ty),
+            &Instr::Truncate(op, ref ty) => cast(w, "truncate", op, ty),
+            &Instr::SignExtend(op, ref ty) => cast(w, "sign_extend", op, ty),
+            &Instr::ZeroExtend(op, ref ty) => cast(w, "zero_extend", op, ty),
+            &Instr::FloatCast(op, ref ty) => cast(w, "float_cast", op, ty),
+            &Instr::FloatToInt(op, ref ty) => cast(w, "float_to_int", op, ty),
+            &Instr::IntToFloat(op, ref ty) => cast(w, "int_to_float", op, ty),
+            Instr::Load(op) => write!(w, "load %op{}", op.index()),
+            Instr::Store { location, value } => write!(w, "store %op{} to %op{}", value.index(), location.index()),
+            Instr::AddressOfStatic(id) => write!(w, "address_of_static %static{}", id.index()),
+            Instr::Pointer { op, is_mut } => write!(w, "pointer {}%op{}", if *is_mut { "mut " } else { "" }, op.index()),
+            Instr::Struct { fields, id } => {
+                write!(w, "struct struct{} ", id.index())?;
+                write_operands(w, fields)
+            },
+            Instr::Enum { variants, id } => {
+                write!(w, "enum enum{} ", id.index())?;
+                write_operands(w, variants)
+            },
+            Instr::StructLit { fields, id } => {
+                write!(w, "struct_lit struct{} ", id.index())?;
+                write_operands(w, fields)
+            },
+            Instr::DirectFieldAccess { val, index } => write!(w, "direct_field_access %op{}, {}", val.index(), index),
+            Instr::IndirectFieldAccess { val, index } => write!(w, "indirect_field_access %op{}, {}", val.index(), index),
+            Instr::Variant { enuum, index, payload } => write!(w, "variant enum{}, {}, %op{}", enuum.index(), index, payload.index()),
+            Instr::DiscriminantAccess { val } => write!(w, "discriminant_access %op{}", val.index()),
+            Instr::ElementAccess { val, index } => write!(w, "element_access %op{}, %op{}", val.index(), index.index()),
+            Instr::ElementAddress { ptr, index } => write!(w, "element_address %op{}, %op{}", ptr.index(), index.index()),
+            Instr::MakeSlice { ptr, len } => write!(w, "make_slice %op{}, %op{}", ptr.index(), len.index()),
+            Instr::SliceLen(op) => write!(w, "slice_len %op{}", op.index()),
+            Instr::Ret(op) => write!(w, "ret %op{}", op.index()),
+            Instr::Br(bb) => write!(w, "br %bb{}", bb.index()),
+            Instr::CondBr { condition, true_bb, false_bb } => write!(
+                w,
+                "cond_br %op{}, %bb{}, %bb{}",
+                condition.index(),
+                true_bb.index(),
+                false_bb.index()
+            ),
+            Instr::SwitchBr { scrutinee, cases, catch_all_bb } => {
+                write!(w, "switch_br %op{} [", scrutinee.index())?;
+                for (i, case) in cases.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    self.write_const(w, &case.value)?;
+                    write!(w, " => %bb{}", case.bb.index())?;
+                }
+                write!(w, "] else %bb{}", catch_all_bb.index())
+            },
+            Instr::GenericParam(id) => write!(w, "generic_param generic_param{}", id.index()),
+            Instr::Parameter(ty) => {
+                write!(w, "parameter ")?;
+                self.write_ty(w, ty)
+            },
+            Instr::Phi { incoming } => {
+                write!(w, "phi [")?;
+                for (i, edge) in incoming.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    write!(w, "%bb{}: %op{}", edge.bb.index(), edge.value.index())?;
+                }
+                write!(w, "]")
+            },
+        }
+    }
+
+    #[display_adapter]
+    pub fn display_ty(&self, ty: &Type, w: &mut Formatter) {
+        self.write_ty(w, ty)
+    }
+
+    /// Writes a function header and body. `id`, if present, is written as `@fN`
+    pub(crate) fn write_func(&self, w: &mut Formatter, id: Option<usize>, func: &Function, name: Option<&str>) -> fmt::Result {
+        write!(w, "fn")?;
+        if let Some(id) = id {
+            write!(w, " @f{}", id)?;
+        }
+        if let Some(name) = name {
+            write!(w, " ")?;
+            write_str_lit(w, name.as_bytes())?;
+        }
+        if !func.generic_params.is_empty() {
+            write!(w, " <")?;
+            for (i, param) in func.generic_params.iter().enumerate() {
+                if i > 0 {
+                    write!(w, ", ")?;
+                }
+                write!(w, "generic_param{}", param.index())?;
+            }
+            write!(w, ">")?;
+        }
+        write!(w, " -> ")?;
+        self.write_ty(w, &func.ret_ty)?;
+        if let Some(decl) = func.decl {
+            write!(w, " decl %decl{}", decl.index())?;
+        }
+        writeln!(w, " {{")?;
+        for &block in &func.blocks {
+            write!(w, "%bb{}:\n{}", block.index(), self.display_block(block))?;
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Displays all strings, statics and functions in `MirCode`, in a form accepted by `Code::parse_mir`
+    #[display_adapter]
+    pub fn display_mir(&self, interner: &StringInterner, w: &mut Formatter) {
+        for (id, string) in self.mir_code.strings.iter_enumerated() {
+            write!(w, "string %str{} = ", id.index())?;
+            write_str_lit(w, CStr::to_bytes(string))?;
+            writeln!(w)?;
+        }
+        for (id, statik) in self.mir_code.statics.iter_enumerated() {
+            write!(w, "static %static{} ", id.index())?;
+            write_str_lit(w, statik.name.as_bytes())?;
+            write!(w, " = ")?;
+            self.write_const(w, &statik.val)?;
+            writeln!(w)?;
+        }
+        for (id, func) in self.mir_code.functions.iter_enumerated() {
+            let name = func.name.and_then(|name| interner.resolve(name));
+            self.write_func(w, Some(id.index()), func, name)?;
+        }
+        Ok(())
+    }
+}
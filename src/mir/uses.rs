@@ -0,0 +1,60 @@
+use crate::mir::Instr;
+use crate::{Code, Op, OpId};
+
+impl Code {
+    /// Appends `instr` to the op arena and records its uses. The op is not added to any block.
+    pub fn push_mir_instr(&mut self, instr: Instr) -> OpId {
+        let op = self.ops.push(Op::MirInstr(instr));
+        self.add_uses(op);
+        op
+    }
+
+    /// Replaces the op `op` with `instr`, updating the use lists of both the old and new operands
+    pub fn set_mir_instr(&mut self, op: OpId, instr: Instr) {
+        self.remove_uses(op);
+        self.ops[op] = Op::MirInstr(instr);
+        self.add_uses(op);
+    }
+
+    /// Returns the MIR instrs that read `op`. A user appears once for each time it reads `op`.
+    /// Stale if `Code::ops` was written to directly since the last `rebuild_uses`.
+    pub fn uses(&self, op: OpId) -> &[OpId] {
+        self.mir_code.uses.get(&op).map(|uses| uses.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn has_uses(&self, op: OpId) -> bool {
+        !self.uses(op).is_empty()
+    }
+
+    /// Recomputes every use list from scratch. Must be called after mutating `Code::ops` directly
+    /// rather than through `push_mir_instr` or `set_mir_instr`, before anything reads the uses.
+    pub fn rebuild_uses(&mut self) {
+        self.mir_code.uses.clear();
+        for op in self.ops.indices() {
+            self.add_uses(op);
+        }
+    }
+
+    pub(crate) fn add_uses(&mut self, op: OpId) {
+        if let Op::MirInstr(instr) = &self.ops[op] {
+            for operand in instr.operands() {
+                self.mir_code.uses.entry(operand).or_default().push(op);
+            }
+        }
+    }
+
+    pub(crate) fn remove_uses(&mut self, op: OpId) {
+        if let Op::MirInstr(instr) = &self.ops[op] {
+            for operand in instr.operands() {
+                if let Some(uses) = self.mir_code.uses.get_mut(&operand) {
+                    if let Some(i) = uses.iter().position(|&user| user == op) {
+                        uses.swap_remove(i);
+                    }
+                    if uses.is_empty() {
+                        self.mir_code.uses.remove(&operand);
+                    }
+                }
+            }
+        }
+    }
+}
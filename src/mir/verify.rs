@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use smallvec::SmallVec;
+
+use crate::mir::{Function, Instr, VOID_INSTR};
+use crate::mir::cfg::Cfg;
+use crate::mir::dom::DomTree;
+use crate::source_info::SourceRange;
+use crate::{Code, Op, BlockId, OpId};
+
+#[derive(Debug)]
+pub enum VerifyErrorKind {
+    /// The block has no ops at all, and therefore no terminator
+    EmptyBlock,
+    /// The last op in the block is not a terminator
+    MissingTerminator,
+    /// A terminator appears somewhere other than the end of the block
+    TerminatorNotAtEnd,
+    /// A `Parameter` instr appears outside the leading run of the entry block
+    MisplacedParameter,
+    /// A `Phi` instr appears after a non-phi instr in its block
+    MisplacedPhi,
+    /// The incoming blocks of a `Phi` are not exactly the predecessors of its block, each listed once
+    PhiIncomingMismatch,
+    /// A block in a MIR function contains an op that is not a MIR instr
+    NonMirOp,
+    /// An operand refers to an op that is not a MIR instr
+    OperandNotMirInstr(OpId),
+    /// An operand refers to a MIR instr that does not dominate the use
+    UseNotDominated(OpId),
+    /// An operand refers to a MIR instr in a different function
+    OperandOutsideFunction(OpId),
+    /// A branch targets a block outside of the function
+    ForeignBranchTarget(BlockId),
+}
+
+#[derive(Debug)]
+pub struct VerifyError {
+    pub kind: VerifyErrorKind,
+    pub block: BlockId,
+    pub op: Option<OpId>,
+    /// The entry in `MirCode::source_ranges` for `op`, if there is one
+    pub range: Option<SourceRange>,
+}
+
+impl Code {
+    /// Checks the structural invariants of `func`, returning every violation found
+    pub fn verify_function(&self, func: &Function) -> Vec<VerifyError> {
+        let mut errors = Vec::new();
+        let mut error = |kind: VerifyErrorKind, block: BlockId, op: Option<OpId>| {
+            let range = op.and_then(|op| self.mir_code.source_ranges.get(&op).copied());
+            errors.push(VerifyError { kind, block, op, range });
+        };
+
+        if func.blocks.is_empty() {
+            return errors;
+        }
+        let func_blocks: HashSet<BlockId> = func.blocks.iter().copied().collect();
+        let func_ops: HashSet<OpId> = func.blocks.iter()
+            .flat_map(|&block| self.blocks[block].ops.iter().copied())
+            .collect();
+        let cfg = Cfg::new(self, func);
+        let dom = DomTree::new(self, func, &cfg);
+        for (i, &block) in func.blocks.iter().enumerate() {
+            let ops = &self.blocks[block].ops;
+            if ops.is_empty() {
+                error(VerifyErrorKind::EmptyBlock, block, None);
+                continue;
+            }
+            // Parameters are only valid in the leading run of the entry block, optionally preceded by void instrs
+            let mut in_param_prefix = i == 0;
+            let mut in_phi_prefix = true;
+            for (j, &id) in ops.iter().enumerate() {
+                let instr = match &self.ops[id] {
+                    Op::MirInstr(instr) => instr,
+                    Op::HirItem(_) => {
+                        error(VerifyErrorKind::NonMirOp, block, Some(id));
+                        in_param_prefix = false;
+                        continue;
+                    },
+                };
+                match instr {
+                    Instr::Void => {},
+                    Instr::Parameter(_) => if !in_param_prefix {
+                        error(VerifyErrorKind::MisplacedParameter, block, Some(id));
+                    },
+                    _ => in_param_prefix = false,
+                }
+                match instr {
+                    Instr::Phi { incoming } => {
+                        if !in_phi_prefix {
+                            error(VerifyErrorKind::MisplacedPhi, block, Some(id));
+                        }
+                        let mut incoming_bbs: Vec<BlockId> = incoming.iter().map(|edge| edge.bb).collect();
+                        let mut predecessors = cfg.predecessors(block).to_vec();
+                        incoming_bbs.sort();
+                        predecessors.sort();
+                        if incoming_bbs != predecessors {
+                            error(VerifyErrorKind::PhiIncomingMismatch, block, Some(id));
+                        }
+                    },
+                    _ => in_phi_prefix = false,
+                }
+                // Each operand is used at its instr, except that a phi's incoming values are used at
+                // the end of the corresponding incoming blocks
+                let uses: SmallVec<[(OpId, BlockId, OpId); 2]> = match instr {
+                    Instr::Phi { incoming } => incoming.iter()
+                        .filter_map(|edge| {
+                            let &last = self.blocks.get(edge.bb)?.ops.last()?;
+                            Some((edge.value, edge.bb, last))
+                        })
+                        .collect(),
+                    _ => instr.operands().into_iter().map(|operand| (operand, block, id)).collect(),
+                };
+                for (operand, use_block, use_op) in uses {
+                    match self.ops.get(operand) {
+                        Some(Op::MirInstr(_)) if operand == VOID_INSTR => {},
+                        Some(Op::MirInstr(_)) => if !func_ops.contains(&operand) {
+                            error(VerifyErrorKind::OperandOutsideFunction(operand), block, Some(id));
+                        } else if cfg.is_reachable(use_block) && (operand == use_op || !dom.op_dominates(operand, use_op)) {
+                            // Dominance is vacuous in unreachable blocks, so uses there aren't checked
+                            error(VerifyErrorKind::UseNotDominated(operand), block, Some(id));
+                        },
+                        _ => error(VerifyErrorKind::OperandNotMirInstr(operand), block, Some(id)),
+                    }
+                }
+                for target in instr.successors() {
+                    if !func_blocks.contains(&target) {
+                        error(VerifyErrorKind::ForeignBranchTarget(target), block, Some(id));
+                    }
+                }
+                if instr.is_terminator() && j + 1 != ops.len() {
+                    error(VerifyErrorKind::TerminatorNotAtEnd, block, Some(id));
+                }
+            }
+            let last = *ops.last().unwrap();
+            let ends_in_terminator = self.ops[last].as_mir_instr()
+                .map(|instr| instr.is_terminator())
+                .unwrap_or(false);
+            if !ends_in_terminator {
+                error(VerifyErrorKind::MissingTerminator, block, Some(last));
+            }
+        }
+        errors
+    }
+}
@@ -1,8 +1,13 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 
+use elsa::FrozenVec;
+use index_vec::{IndexVec, define_index_type};
+
 use crate::arch::Arch;
 use crate::hir::{StructId, EnumId, GenericParamId};
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum IntWidth {
     W8, W16, W32, W64, Pointer,
 }
@@ -19,21 +24,32 @@ impl IntWidth {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum FloatWidth {
     W32, W64,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+define_index_type!(pub struct TypeId = u32;);
+define_index_type!(pub struct TypeListId = u32;);
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
 pub enum Type {
+    #[default]
     Error,
     Int {
         width: IntWidth,
         is_signed: bool,
     },
     Float(FloatWidth),
-    // TODO: Eliminate this separate heap allocation by interning all types into an IndexVec
-    Pointer(Box<QualType>),
+    /// The pointee is interned in a `TypeInterner`
+    Pointer { pointee: TypeId, is_mut: bool },
+    /// `len` elements of the interned type `elem`, stored one after another
+    Array { elem: TypeId, len: usize },
+    /// A pointer to a run of elements of the interned type `elem`, together with their number
+    Slice { elem: TypeId, is_mut: bool },
+    /// A pointer to a function taking the interned list of types `params` and returning the
+    /// interned type `ret`
+    Function { params: TypeListId, ret: TypeId },
     Struct(StructId),
     Enum(EnumId),
     Bool,
@@ -45,26 +61,20 @@ pub enum Type {
 }
 
 impl Type {
-    pub fn ptr(self) -> Self {
-        self.ptr_with_mut(false)
+    pub fn ptr(self, types: &TypeInterner) -> Self {
+        types.ptr(self)
     }
 
-    pub fn mut_ptr(self) -> Self {
-        self.ptr_with_mut(true)
+    pub fn mut_ptr(self, types: &TypeInterner) -> Self {
+        types.mut_ptr(self)
     }
 
-    pub fn ptr_with_mut(self, is_mut: bool) -> Self {
-        Type::Pointer(
-            Box::new(QualType { ty: self, is_mut })
-        )
+    pub fn ptr_with_mut(self, is_mut: bool, types: &TypeInterner) -> Self {
+        types.ptr_with_mut(self, is_mut)
     }
 
-    pub fn deref(self) -> Option<QualType> {
-        if let Type::Pointer(pointee) = self {
-            Some(*pointee)
-        } else {
-            None
-        }
+    pub fn deref(self, types: &TypeInterner) -> Option<QualType> {
+        types.deref(self)
     }
 
     pub const fn u8() -> Self {
@@ -115,19 +125,8 @@ impl Type {
         Type::Float(FloatWidth::W64)
     }
 
-    pub fn trivially_convertible_to(&self, other: &Type) -> bool {
-        match (self, other) {
-            (Type::Never, _other) => true,
-            (Type::Pointer(a), Type::Pointer(b)) => a.trivially_convertible_to(b),
-            (_self, Type::GenericParam(_)) => true,
-            (a, b) => a == b,
-        }
-    }
-}
-
-impl Default for Type {
-    fn default() -> Self {
-        Type::Error
+    pub fn trivially_convertible_to(&self, other: &Type, types: &TypeInterner) -> bool {
+        types.trivially_convertible_to(*self, *other)
     }
 }
 
@@ -160,14 +159,25 @@ impl fmt::Debug for Type {
                     FloatWidth::W64 => 64,
                 }
             ),
-            Type::Pointer(pointee) => {
-                pointee.ty.fmt(f)?;
-                if pointee.is_mut { 
+            // The pointee can't be printed without access to the `TypeInterner`; `Code::display_ty`
+            // prints it
+            &Type::Pointer { pointee, is_mut } => {
+                write!(f, "ty{}", pointee.index())?;
+                if is_mut {
                     write!(f, " *mut")
                 } else {
                     write!(f, "*")
                 }
             },
+            &Type::Array { elem, len } => write!(f, "[ty{}; {}]", elem.index(), len),
+            &Type::Slice { elem, is_mut } => {
+                write!(f, "[]")?;
+                if is_mut {
+                    write!(f, "mut ")?;
+                }
+                write!(f, "ty{}", elem.index())
+            },
+            &Type::Function { params, ret } => write!(f, "fn(types{}) -> ty{}", params.index(), ret.index()),
             // TODO: print out fields (issue #76)
             &Type::Struct(id) => {
                 write!(f, "struct{}", id.index())
@@ -182,22 +192,19 @@ impl fmt::Debug for Type {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct QualType {
     pub ty: Type,
     pub is_mut: bool,
 }
 
 impl QualType {
-    pub fn trivially_convertible_to(&self, other: &QualType) -> bool {
-        if !self.is_mut && other.is_mut {
-            return false;
-        }
-        self.ty.trivially_convertible_to(&other.ty)
+    pub fn trivially_convertible_to(&self, other: &QualType, types: &TypeInterner) -> bool {
+        types.qual_trivially_convertible_to(*self, *other)
     }
 
-    pub fn ptr(self) -> Type {
-        Type::Pointer(Box::new(self))
+    pub fn ptr(self, types: &TypeInterner) -> Type {
+        types.qual_ptr(self)
     }
 }
 
@@ -210,8 +217,154 @@ impl From<Type> for QualType {
     }
 }
 
-impl<'a> From<&Type> for QualType {
+impl From<&Type> for QualType {
     fn from(ty: &Type) -> Self {
-        QualType::from(ty.clone())
+        QualType::from(*ty)
     }
-}
\ No newline at end of file
+}
+
+/// Interns the types that other types refer to, like pointees and parameter lists, so that `Type`
+/// can be `Copy`.
+/// Interning only needs a shared reference, so types can be created while `Code` is borrowed
+/// immutably, e.g. by the interpreter when substituting generic arguments.
+#[derive(Default)]
+pub struct TypeInterner {
+    types: RefCell<IndexVec<TypeId, Type>>,
+    ids: RefCell<HashMap<Type, TypeId>>,
+    /// Lists are boxed and never removed, so references to them stay valid as more are added
+    lists: FrozenVec<Box<[Type]>>,
+    list_ids: RefCell<HashMap<Vec<Type>, TypeListId>>,
+}
+
+impl fmt::Debug for TypeInterner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypeInterner")
+            .field("types", &self.types)
+            .field("ids", &self.ids)
+            .field("lists", &self.lists.iter().collect::<Vec<_>>())
+            .field("list_ids", &self.list_ids)
+            .finish()
+    }
+}
+
+impl TypeInterner {
+    pub fn intern(&self, ty: Type) -> TypeId {
+        if let Some(&id) = self.ids.borrow().get(&ty) {
+            return id;
+        }
+        let id = self.types.borrow_mut().push(ty);
+        self.ids.borrow_mut().insert(ty, id);
+        id
+    }
+
+    pub fn get(&self, id: TypeId) -> Type {
+        self.types.borrow()[id]
+    }
+
+    pub fn intern_list(&self, tys: &[Type]) -> TypeListId {
+        if let Some(&id) = self.list_ids.borrow().get(tys) {
+            return id;
+        }
+        let id = TypeListId::new(self.lists.len());
+        self.lists.push(tys.into());
+        self.list_ids.borrow_mut().insert(tys.to_vec(), id);
+        id
+    }
+
+    pub fn get_list(&self, id: TypeListId) -> &[Type] {
+        &self.lists[id.index()]
+    }
+
+    pub fn ptr(&self, ty: Type) -> Type {
+        self.ptr_with_mut(ty, false)
+    }
+
+    pub fn mut_ptr(&self, ty: Type) -> Type {
+        self.ptr_with_mut(ty, true)
+    }
+
+    pub fn ptr_with_mut(&self, ty: Type, is_mut: bool) -> Type {
+        Type::Pointer { pointee: self.intern(ty), is_mut }
+    }
+
+    pub fn qual_ptr(&self, ty: QualType) -> Type {
+        self.ptr_with_mut(ty.ty, ty.is_mut)
+    }
+
+    pub fn deref(&self, ty: Type) -> Option<QualType> {
+        if let Type::Pointer { pointee, is_mut } = ty {
+            Some(QualType { ty: self.get(pointee), is_mut })
+        } else {
+            None
+        }
+    }
+
+    pub fn array(&self, elem: Type, len: usize) -> Type {
+        Type::Array { elem: self.intern(elem), len }
+    }
+
+    pub fn slice(&self, elem: Type, is_mut: bool) -> Type {
+        Type::Slice { elem: self.intern(elem), is_mut }
+    }
+
+    pub fn function(&self, params: &[Type], ret: Type) -> Type {
+        Type::Function { params: self.intern_list(params), ret: self.intern(ret) }
+    }
+
+    /// The parameter and return types of a function pointer type
+    pub fn signature(&self, ty: Type) -> Option<(&[Type], Type)> {
+        match ty {
+            Type::Function { params, ret } => Some((self.get_list(params), self.get(ret))),
+            _ => None,
+        }
+    }
+
+    /// The element type of an array or slice
+    pub fn elem(&self, ty: Type) -> Option<QualType> {
+        match ty {
+            Type::Array { elem, .. } => Some(QualType::from(self.get(elem))),
+            Type::Slice { elem, is_mut } => Some(QualType { ty: self.get(elem), is_mut }),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of type `ty` can be used where `other` is expected. A pointer to an array
+    /// converts to a slice of its elements, which is a different representation, so that
+    /// conversion needs a `MakeSlice` instr in MIR. A function pointer converts to another with the
+    /// same parameter types if its return type converts.
+    pub fn trivially_convertible_to(&self, ty: Type, other: Type) -> bool {
+        match (ty, other) {
+            (Type::Never, _other) => true,
+            (Type::Pointer { .. }, Type::Pointer { .. }) | (Type::Slice { .. }, Type::Slice { .. }) => {
+                let (a, b) = (self.pointee_or_elem(ty).unwrap(), self.pointee_or_elem(other).unwrap());
+                self.qual_trivially_convertible_to(a, b)
+            },
+            (Type::Array { len: a_len, .. }, Type::Array { len: b_len, .. }) => {
+                a_len == b_len && self.trivially_convertible_to(self.elem(ty).unwrap().ty, self.elem(other).unwrap().ty)
+            },
+            (Type::Function { params: a_params, ret: a_ret }, Type::Function { params: b_params, ret: b_ret }) => {
+                a_params == b_params && self.trivially_convertible_to(self.get(a_ret), self.get(b_ret))
+            },
+            (Type::Pointer { .. }, Type::Slice { .. }) => match self.deref(ty).unwrap() {
+                QualType { ty: array @ Type::Array { .. }, is_mut } => {
+                    let elem = QualType { ty: self.elem(array).unwrap().ty, is_mut };
+                    self.qual_trivially_convertible_to(elem, self.elem(other).unwrap())
+                },
+                _ => false,
+            },
+            (_ty, Type::GenericParam(_)) => true,
+            (a, b) => a == b,
+        }
+    }
+
+    fn pointee_or_elem(&self, ty: Type) -> Option<QualType> {
+        self.deref(ty).or_else(|| self.elem(ty))
+    }
+
+    pub fn qual_trivially_convertible_to(&self, ty: QualType, other: QualType) -> bool {
+        if !ty.is_mut && other.is_mut {
+            return false;
+        }
+        self.trivially_convertible_to(ty.ty, other.ty)
+    }
+}
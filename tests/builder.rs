@@ -0,0 +1,44 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::hir::Intrinsic;
+use dire::mir::Const;
+use dire::mir::builder::{MirBuilder, BuildError};
+use dire::mir::interp::Interpreter;
+use dire::source_info::SourceRange;
+use dire::ty::Type;
+use smallvec::smallvec;
+
+#[test]
+fn build_and_run_function() {
+    let mut code = Code::default();
+    let range = SourceRange::default();
+    let mut b = MirBuilder::create_function(&mut code, None, Type::u8());
+    let condition = b.parameter(Type::Bool, range).unwrap();
+    let x = b.parameter(Type::u8(), range).unwrap();
+    assert_eq!(b.name(x, "x"), "x");
+    let one = b.konst(Const::Int { lit: 1, ty: Type::u8() }, range).unwrap();
+    assert_eq!(b.name(one, "x"), "x.1");
+    assert!(matches!(b.parameter(Type::u8(), range), Err(BuildError::MisplacedParameter)));
+
+    let then_bb = b.create_block();
+    let else_bb = b.create_block();
+    b.cond_br(condition, then_bb, else_bb, range).unwrap();
+    assert!(matches!(b.void(range), Err(BuildError::BlockEnded(_))));
+    b.switch_to_block(then_bb).unwrap();
+    let sum = b.intrinsic(Intrinsic::Add, smallvec![x, one], Type::u8(), range).unwrap();
+    b.ret(sum, range).unwrap();
+    b.switch_to_block(else_bb).unwrap();
+    let func = b.func();
+    assert!(matches!(b.finish(), Err(BuildError::BlockNotEnded(block)) if block == else_bb));
+
+    let mut b = MirBuilder::new(&mut code, func);
+    b.switch_to_block(else_bb).unwrap();
+    b.ret(x, range).unwrap();
+    let func = b.finish().unwrap();
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    let x = Const::Int { lit: 41, ty: Type::u8() };
+    assert_eq!(interpreter.eval(func, vec![Const::Bool(true), x.clone()], vec![]).unwrap(), Const::Int { lit: 42, ty: Type::u8() });
+    assert_eq!(interpreter.eval(func, vec![Const::Bool(false), x.clone()], vec![]).unwrap(), x);
+}
@@ -0,0 +1,45 @@
+use dire::{Code, BlockId};
+use dire::mir::FuncId;
+use dire::mir::cfg::Cfg;
+use string_interner::StringInterner;
+
+#[test]
+fn edges_orders_and_reachability() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op3 = mir.br %bb3
+%bb2:
+    %op4 = mir.br %bb3
+%bb3:
+    %op5 = mir.cond_br %op1, %bb1, %bb5
+%bb4:
+    %op6 = mir.br %bb3
+%bb5:
+    %op7 = mir.ret %op0
+}
+"#, &mut interner).unwrap();
+    let cfg = Cfg::new(&code, &code.mir_code.functions[FuncId::new(0)]);
+    let b = BlockId::new;
+    assert_eq!(cfg.entry(), b(0));
+    assert_eq!(cfg.successors(b(0)), &[b(1), b(2)]);
+    assert_eq!(cfg.successors(b(3)), &[b(1), b(5)]);
+    assert_eq!(cfg.predecessors(b(3)), &[b(1), b(2), b(4)]);
+    assert!(cfg.predecessors(b(0)).is_empty());
+
+    let rpo = cfg.reverse_postorder();
+    assert_eq!(rpo.len(), 5);
+    assert_eq!(rpo[0], b(0));
+    let position = |block: BlockId| rpo.iter().position(|&other| other == block).unwrap();
+    assert!(position(b(1)) < position(b(3)));
+    assert!(position(b(2)) < position(b(3)));
+    assert!(position(b(3)) < position(b(5)));
+
+    assert!(!cfg.is_reachable(b(4)));
+    assert_eq!(cfg.unreachable_blocks(), vec![b(4)]);
+    assert_eq!(cfg.exits(&code), vec![b(5)]);
+}
@@ -0,0 +1,35 @@
+use dire::Code;
+use dire::mir::{FuncId, Instr};
+use string_interner::StringInterner;
+
+#[test]
+fn removes_unreachable_blocks_and_unused_instrs() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const int u8 1
+    %op2 = mir.intrinsic Add u8 (%op1, %op1)
+    %op3 = mir.intrinsic Mult u8 (%op2, %op2)
+    %op4 = mir.br %bb2
+%bb1:
+    %op5 = mir.intrinsic Add u8 (%op6, %op1)
+    %op6 = mir.intrinsic Add u8 (%op5, %op1)
+    %op7 = mir.br %bb2
+%bb2:
+    %op8 = mir.ret %op1
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let (one, ret) = (code.blocks[blocks[0]].ops[0], code.blocks[blocks[2]].ops[0]);
+
+    assert_eq!(code.eliminate_dead_code(func), 6);
+    assert_eq!(code.mir_code.functions[func].blocks, vec![blocks[0], blocks[2]]);
+    assert_eq!(code.blocks[blocks[0]].ops.len(), 2);
+    assert_eq!(code.blocks[blocks[0]].ops[0], one);
+    assert_eq!(code.blocks[blocks[2]].ops, vec![ret]);
+    assert_eq!(code.uses(one), &[ret]);
+    assert!(matches!(code.ops[one].as_mir_instr(), Some(Instr::Const(_))));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
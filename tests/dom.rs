@@ -0,0 +1,35 @@
+use dire::Code;
+use dire::mir::FuncId;
+use dire::mir::cfg::Cfg;
+use dire::mir::dom::{DomTree, DominanceFrontiers};
+use string_interner::StringInterner;
+
+#[test]
+fn post_dominators_with_two_returns() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op3 = mir.ret %op0
+%bb2:
+    %op4 = mir.ret %op0
+}
+"#, &mut interner).unwrap();
+    let func = &code.mir_code.functions[FuncId::new(0)];
+    let cfg = Cfg::new(&code, func);
+    let b = |i: usize| func.blocks[i];
+    let pdom = DomTree::new_post(&code, func, &cfg);
+    for i in 0..3 {
+        assert!(pdom.contains(b(i)));
+        assert_eq!(pdom.idom(b(i)), None);
+    }
+    assert_eq!(pdom.roots(), &[b(0), b(1), b(2)]);
+    assert!(!pdom.dominates(b(1), b(0)));
+    assert!(pdom.dominates(b(0), b(0)));
+    let frontiers = DominanceFrontiers::new(&cfg, &pdom);
+    assert_eq!(frontiers.frontier(b(1)), &[b(0)]);
+    assert_eq!(frontiers.frontier(b(2)), &[b(0)]);
+}
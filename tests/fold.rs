@@ -0,0 +1,55 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId, Instr};
+use dire::mir::eval::EvalError;
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+const SRC: &str = r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.const int u8 200
+    %op2 = mir.const int u8 100
+    %op3 = mir.intrinsic Add u8 (%op1, %op2)
+    %op4 = mir.sign_extend %op1 to i16
+    %op5 = mir.const int usize 65535
+    %op6 = mir.const int usize 1
+    %op7 = mir.intrinsic Add usize (%op5, %op6)
+    %op8 = mir.const int u8 0
+    %op9 = mir.intrinsic Div u8 (%op1, %op8)
+    %op10 = mir.ret %op0
+}
+"#;
+
+fn fold(arch: Arch) -> (Code, Vec<EvalError>) {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let (num_folded, errors) = code.fold_constants(FuncId::new(0), arch);
+    assert_eq!(num_folded, 3);
+    (code, errors.into_iter().map(|error| error.kind).collect())
+}
+
+fn folded(code: &Code, index: usize) -> &Const {
+    let block = code.mir_code.functions[FuncId::new(0)].blocks[0];
+    match code.ops[code.blocks[block].ops[index]].as_mir_instr() {
+        Some(Instr::Const(konst)) => konst,
+        instr => panic!("expected a constant, found {:?}", instr),
+    }
+}
+
+#[test]
+fn folds_with_wrapping_and_reports_traps() {
+    let (code, errors) = fold(Arch::X86_64);
+    assert_eq!(folded(&code, 2), &Const::Int { lit: 44, ty: Type::u8() });
+    assert_eq!(folded(&code, 3), &Const::Int { lit: 0xFFC8, ty: Type::i16() });
+    assert_eq!(folded(&code, 6), &Const::Int { lit: 65536, ty: Type::usize() });
+    assert_eq!(errors, vec![EvalError::DivideByZero]);
+    let block = code.mir_code.functions[FuncId::new(0)].blocks[0];
+    assert!(matches!(code.ops[code.blocks[block].ops[8]].as_mir_instr(), Some(Instr::Intrinsic { .. })));
+}
+
+#[test]
+fn pointer_width_follows_arch() {
+    let (code, _) = fold(Arch::SharpLR35902);
+    assert_eq!(folded(&code, 6), &Const::Int { lit: 0, ty: Type::usize() });
+}
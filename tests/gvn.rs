@@ -0,0 +1,39 @@
+use dire::Code;
+use dire::mir::{FuncId, Instr};
+use string_interner::StringInterner;
+
+#[test]
+fn dominated_duplicates_are_replaced() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter u8
+    %op2 = mir.parameter u8
+    %op3 = mir.parameter bool
+    %op4 = mir.intrinsic Add u8 (%op1, %op2)
+    %op5 = mir.cond_br %op3, %bb1, %bb2
+%bb1:
+    %op6 = mir.intrinsic Add u8 (%op2, %op1)
+    %op7 = mir.intrinsic Mult u8 (%op1, %op2)
+    %op8 = mir.intrinsic Sub u8 (%op6, %op7)
+    %op9 = mir.ret %op8
+%bb2:
+    %op10 = mir.intrinsic Mult u8 (%op1, %op2)
+    %op11 = mir.ret %op10
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let sum = code.blocks[blocks[0]].ops[3];
+    let then_ops = code.blocks[blocks[1]].ops.clone();
+    let else_ops = code.blocks[blocks[2]].ops.clone();
+
+    // The commuted `Add` is dominated by the first one, but neither `Mult` dominates the other
+    assert_eq!(code.gvn(func), 1);
+    assert_eq!(code.blocks[blocks[1]].ops, &then_ops[1..]);
+    assert_eq!(code.blocks[blocks[2]].ops, else_ops);
+    let difference = code.ops[then_ops[2]].as_mir_instr().unwrap();
+    assert!(matches!(difference, Instr::Intrinsic { arguments, .. } if arguments[..] == [sum, then_ops[1]]));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
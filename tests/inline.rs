@@ -0,0 +1,139 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::hir::{Attribute, DeclId};
+use dire::mir::{Const, FuncId, Instr};
+use dire::mir::inline::InlineOptions;
+use dire::mir::interp::Interpreter;
+use dire::source_info::SourceRange;
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+fn has_calls(code: &Code, func: FuncId) -> bool {
+    code.mir_code.functions[func].blocks.iter()
+        .flat_map(|&block| &code.blocks[block].ops)
+        .any(|&op| matches!(code.ops[op].as_mir_instr(), Some(Instr::Call { .. })))
+}
+
+#[test]
+fn parameters_become_arguments() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const int u8 5
+    %op2 = mir.const int u8 3
+    %op3 = mir.call @f1(%op1, %op2)
+    %op4 = mir.ret %op3
+}
+
+fn @f1 "sub" -> u8 {
+%bb1:
+    %op5 = mir.parameter u8
+    %op6 = mir.parameter u8
+    %op7 = mir.intrinsic Sub u8 (%op5, %op6)
+    %op8 = mir.ret %op7
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let entry = code.mir_code.functions[func].blocks[0];
+    let (five, three) = (code.blocks[entry].ops[0], code.blocks[entry].ops[1]);
+    assert_eq!(code.inline_calls(func, &InlineOptions::default()), 1);
+    assert!(!has_calls(&code, func));
+
+    let sub = code.mir_code.functions[func].blocks.iter()
+        .flat_map(|&block| &code.blocks[block].ops)
+        .find_map(|&op| match code.ops[op].as_mir_instr() {
+            Some(Instr::Intrinsic { arguments, .. }) => Some(arguments.to_vec()),
+            _ => None,
+        });
+    assert_eq!(sub, Some(vec![five, three]));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+    let result = Interpreter::new(&code, Arch::X86_64).eval(func, vec![], vec![]).unwrap();
+    assert_eq!(result, Const::Int { lit: 2, ty: Type::u8() });
+}
+
+#[test]
+fn returns_branch_to_the_continuation() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.call @f1(%op1)
+    %op3 = mir.ret %op2
+}
+
+fn @f1 "choose" -> u8 {
+%bb1:
+    %op4 = mir.parameter bool
+    %op5 = mir.cond_br %op4, %bb2, %bb3
+%bb2:
+    %op6 = mir.const int u8 1
+    %op7 = mir.ret %op6
+%bb3:
+    %op8 = mir.const int u8 2
+    %op9 = mir.ret %op8
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    assert_eq!(code.inline_calls(func, &InlineOptions::default()), 1);
+    assert!(!has_calls(&code, func));
+
+    // The entry block, a copy of each callee block, then the continuation
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    assert_eq!(blocks.len(), 5);
+    let continuation = blocks[4];
+    assert_eq!(code.terminator(blocks[0]), Some(&Instr::Br(blocks[1])));
+    for &block in &blocks[2..4] {
+        assert_eq!(code.terminator(block), Some(&Instr::Br(continuation)));
+    }
+    let phi = code.blocks[continuation].ops[0];
+    match code.ops[phi].as_mir_instr() {
+        Some(Instr::Phi { incoming }) => {
+            let mut incoming_bbs: Vec<_> = incoming.iter().map(|edge| edge.bb).collect();
+            incoming_bbs.sort();
+            assert_eq!(incoming_bbs, &blocks[2..4]);
+        },
+        instr => panic!("expected a phi, found {:?}", instr),
+    }
+    assert_eq!(code.terminator(continuation), Some(&Instr::Ret(phi)));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    for &(condition, lit) in &[(true, 1), (false, 2)] {
+        let result = interpreter.eval(func, vec![Const::Bool(condition)], vec![]).unwrap();
+        assert_eq!(result, Const::Int { lit, ty: Type::u8() });
+    }
+}
+
+#[test]
+fn inline_attribute_overrides_max_size() {
+    let mut interner = StringInterner::default();
+    let src = r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.call @f1()
+    %op2 = mir.ret %op1
+}
+
+fn @f1 "one" -> u8 {
+%bb1:
+    %op3 = mir.const int u8 1
+    %op4 = mir.ret %op3
+}
+"#;
+    let inline = interner.get_or_intern("inline");
+    let options = InlineOptions { max_size: 1, inline_attr: Some(inline) };
+    let func = FuncId::new(0);
+
+    let mut code = Code::parse_mir(src, &mut interner).unwrap();
+    assert_eq!(code.inline_calls(func, &options), 0);
+    assert!(has_calls(&code, func));
+
+    let mut code = Code::parse_mir(src, &mut interner).unwrap();
+    let decl = DeclId::new(0);
+    code.mir_code.functions[FuncId::new(1)].decl = Some(decl);
+    code.hir_code.decl_attributes.insert(decl, vec![Attribute { attr: inline, arg: None, range: SourceRange::default() }]);
+    assert_eq!(code.inline_calls(func, &options), 1);
+    assert!(!has_calls(&code, func));
+}
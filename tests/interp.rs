@@ -0,0 +1,192 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId};
+use dire::mir::interp::{Interpreter, InterpErrorKind, DEFAULT_MAX_CALL_DEPTH};
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+#[test]
+fn eval_returns_array() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> [u8; 2] {
+%bb0:
+    %op1 = mir.alloca [u8; 2]
+    %op2 = mir.const int usize 0
+    %op3 = mir.element_address %op1, %op2
+    %op4 = mir.const int u8 7
+    %op5 = mir.store %op4 to %op3
+    %op6 = mir.const int usize 1
+    %op7 = mir.element_address %op1, %op6
+    %op8 = mir.store %op4 to %op7
+    %op9 = mir.load %op1
+    %op10 = mir.ret %op9
+}
+"#, &mut interner).unwrap();
+    let result = Interpreter::new(&code, Arch::X86_64).eval(FuncId::new(0), vec![], vec![]).unwrap();
+    let seven = Const::Int { lit: 7, ty: Type::u8() };
+    assert_eq!(result, Const::Array { elems: vec![seven.clone(), seven], ty: code.types.array(Type::u8(), 2) });
+}
+
+#[test]
+fn runaway_recursion_is_an_error() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.call @f0()
+    %op2 = mir.ret %op1
+}
+"#, &mut interner).unwrap();
+    let depth_exceeded = |interpreter: &mut Interpreter| {
+        let mut error = interpreter.eval(FuncId::new(0), vec![], vec![]).unwrap_err();
+        let mut depth = 1;
+        while let InterpErrorKind::InCallee(callee) = error.kind {
+            error = *callee;
+            depth += 1;
+        }
+        assert!(matches!(error.kind, InterpErrorKind::CallDepthExceeded(_)));
+        depth
+    };
+    assert_eq!(depth_exceeded(&mut Interpreter::new(&code, Arch::X86_64)), DEFAULT_MAX_CALL_DEPTH + 1);
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    interpreter.set_max_call_depth(10);
+    assert_eq!(depth_exceeded(&mut interpreter), 11);
+}
+
+#[test]
+fn load_through_string() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+string %str0 = "hi"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const str *u8 %str0
+    %op2 = mir.const int usize 2
+    %op3 = mir.make_slice %op1, %op2
+    %op4 = mir.const int usize 1
+    %op5 = mir.element_address %op3, %op4
+    %op6 = mir.load %op5
+    %op7 = mir.load %op1
+    %op8 = mir.intrinsic Add u8 (%op6, %op7)
+    %op9 = mir.ret %op8
+}
+"#, &mut interner).unwrap();
+    let result = Interpreter::new(&code, Arch::X86_64).eval(FuncId::new(0), vec![], vec![]).unwrap();
+    assert_eq!(result, Const::Int { lit: (b'h' + b'i') as u64, ty: Type::u8() });
+}
+
+#[test]
+fn print_writes_to_output() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+string %str0 = "x = "
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.const str *u8 %str0
+    %op2 = mir.const int i8 253
+    %op3 = mir.intrinsic Print void (%op1, %op2)
+    %op4 = mir.const type u16
+    %op5 = mir.intrinsic PrintType void (%op4)
+    %op6 = mir.ret %op0
+}
+"#, &mut interner).unwrap();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    interpreter.set_output(Box::new(&mut output));
+    interpreter.call(FuncId::new(0), vec![], vec![]).unwrap();
+    drop(interpreter);
+    assert_eq!(String::from_utf8(output).unwrap(), "x = -3u16");
+}
+
+#[test]
+fn phi_takes_value_from_incoming_block() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.const int u8 1
+    %op3 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op4 = mir.const int u8 2
+    %op5 = mir.br %bb2
+%bb2:
+    %op6 = mir.phi [%bb0: %op2, %bb1: %op4]
+    %op7 = mir.ret %op6
+}
+"#, &mut interner).unwrap();
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    for &(condition, lit) in &[(true, 2), (false, 1)] {
+        let result = interpreter.eval(FuncId::new(0), vec![Const::Bool(condition)], vec![]).unwrap();
+        assert_eq!(result, Const::Int { lit, ty: Type::u8() });
+    }
+}
+
+#[test]
+fn struct_fields_are_not_arrays() {
+    let mut interner = StringInterner::default();
+    let src = |instr: &str| format!(r#"
+fn @f0 "f" -> void {{
+%bb0:
+    %op1 = mir.const type u8
+    %op2 = mir.struct struct0 (%op1, %op1)
+    %op3 = mir.alloca struct0
+    %op4 = mir.const int u8 7
+    %op5 = mir.struct_lit struct0 (%op4, %op4)
+    %op6 = mir.store %op5 to %op3
+    %op7 = mir.indirect_field_access %op3, 0
+    %op8 = mir.const int usize 1
+    %op9 = mir.{} %op7, %op8
+    %op10 = mir.ret %op0
+}}
+"#, instr);
+    for instr in &["make_slice", "element_address"] {
+        let code = Code::parse_mir(&src(instr), &mut interner).unwrap();
+        let error = Interpreter::new(&code, Arch::X86_64).call(FuncId::new(0), vec![], vec![]).unwrap_err();
+        assert!(matches!(error.kind, InterpErrorKind::InvalidOperands), "{}: {:?}", instr, error);
+    }
+}
+
+#[test]
+fn indirect_calls_check_the_callee_signature() {
+    let mut interner = StringInterner::default();
+    let src = |callee: &str, argument: &str| format!(r#"
+fn @f0 "f" -> u8 {{
+%bb0:
+    %op1 = mir.const {}
+    %op2 = mir.const {}
+    %op3 = mir.call_indirect %op1(%op2)
+    %op4 = mir.ret %op3
+}}
+
+fn @f1 "double" -> u8 {{
+%bb1:
+    %op5 = mir.parameter u8
+    %op6 = mir.intrinsic Add u8 (%op5, %op5)
+    %op7 = mir.ret %op6
+}}
+
+fn @f2 "id" <generic_param0> -> generic_param0 {{
+%bb2:
+    %op8 = mir.parameter generic_param0
+    %op9 = mir.ret %op8
+}}
+"#, callee, argument);
+    let call = |callee: &str, argument: &str, interner: &mut StringInterner| {
+        let code = Code::parse_mir(&src(callee, argument), interner).unwrap();
+        let result = Interpreter::new(&code, Arch::X86_64).eval(FuncId::new(0), vec![], vec![]);
+        result.map_err(|error| error.kind)
+    };
+
+    let result = call("func fn(u8) -> u8 @f1", "int u8 4", &mut interner).unwrap();
+    assert_eq!(result, Const::Int { lit: 8, ty: Type::u8() });
+    for &(callee, argument) in &[
+        ("func fn(u16) -> u8 @f1", "int u16 4"),
+        ("func fn(u8) -> u8 @f1", "int u16 4"),
+        ("func fn(u8) -> u8 @f2", "int u8 4"),
+    ] {
+        let result = call(callee, argument, &mut interner);
+        assert!(matches!(result, Err(InterpErrorKind::InvalidOperands)), "{}: {:?}", callee, result);
+    }
+}
@@ -0,0 +1,55 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::hir::{EnumId, StructId};
+use dire::mir::{Const, DiscriminantEncoding};
+use dire::ty::Type;
+use smallvec::smallvec;
+
+#[test]
+fn null_pointer_niche_depends_on_target() {
+    let mut code = Code::default();
+    let ptr = code.types.ptr(Type::u8());
+    code.register_enum(EnumId::new(0), smallvec![ptr, Type::Void], Arch::X86_64).unwrap();
+    let layout = &code.mir_code.enums[&EnumId::new(0)].layout;
+    assert!(matches!(layout.discriminant, DiscriminantEncoding::Niche { niche_start: 0, .. }));
+    assert_eq!(layout.size, 8);
+
+    code.register_enum(EnumId::new(0), smallvec![ptr, Type::Void], Arch::SharpLR35902).unwrap();
+    let layout = &code.mir_code.enums[&EnumId::new(0)].layout;
+    assert!(matches!(layout.discriminant, DiscriminantEncoding::Tag { .. }));
+}
+
+#[test]
+fn struct_layout_follows_pointer_size_and_alignment() {
+    let code = Code::default();
+    let fields = [Type::u8(), code.types.ptr(Type::u8()), Type::u16()];
+
+    let layout = code.struct_layout(&fields, Arch::X86_64).unwrap();
+    assert_eq!(&layout.field_offsets[..], &[0, 8, 16]);
+    assert_eq!((layout.size, layout.alignment, layout.stride), (18, 8, 24));
+
+    // Nothing needs to be aligned on the Gameboy, so there is no padding
+    let layout = code.struct_layout(&fields, Arch::SharpLR35902).unwrap();
+    assert_eq!(&layout.field_offsets[..], &[0, 1, 3]);
+    assert_eq!((layout.size, layout.alignment, layout.stride), (5, 1, 5));
+}
+
+#[test]
+fn constants_serialize_with_target_padding_and_width() {
+    let mut code = Code::default();
+    let id = StructId::new(0);
+    let fields = smallvec![Type::u8(), Type::u32()];
+    let strukt = Const::StructLit {
+        fields: vec![Const::Int { lit: 0xAA, ty: Type::u8() }, Const::Int { lit: 0x0102_0304, ty: Type::u32() }],
+        id,
+    };
+
+    code.register_struct(id, fields.clone(), Arch::X86_64).unwrap();
+    assert_eq!(code.serialize_const(&strukt, Arch::X86_64).unwrap(), vec![0xAA, 0, 0, 0, 4, 3, 2, 1]);
+    code.register_struct(id, fields, Arch::SharpLR35902).unwrap();
+    assert_eq!(code.serialize_const(&strukt, Arch::SharpLR35902).unwrap(), vec![0xAA, 4, 3, 2, 1]);
+
+    let address = Const::Int { lit: 0x1234, ty: Type::usize() };
+    assert_eq!(code.serialize_const(&address, Arch::X86_64).unwrap(), vec![0x34, 0x12, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(code.serialize_const(&address, Arch::SharpLR35902).unwrap(), vec![0x34, 0x12]);
+}
@@ -0,0 +1,55 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId, Instr};
+use dire::mir::interp::Interpreter;
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+#[test]
+fn invariants_are_hoisted_into_a_new_preheader() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter u8
+    %op2 = mir.parameter u8
+    %op3 = mir.parameter u8
+    %op4 = mir.const int u8 0
+    %op5 = mir.intrinsic Less bool (%op4, %op3)
+    %op6 = mir.cond_br %op5, %bb1, %bb2
+%bb1:
+    %op7 = mir.phi [%bb0: %op4, %bb1: %op10]
+    %op8 = mir.phi [%bb0: %op4, %bb1: %op11]
+    %op9 = mir.intrinsic Mult u8 (%op1, %op2)
+    %op10 = mir.intrinsic Add u8 (%op7, %op9)
+    %op11 = mir.intrinsic Add u8 (%op8, %op1)
+    %op12 = mir.intrinsic Less bool (%op11, %op3)
+    %op13 = mir.cond_br %op12, %bb1, %bb2
+%bb2:
+    %op14 = mir.phi [%bb0: %op4, %bb1: %op10]
+    %op15 = mir.ret %op14
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let product = code.blocks[blocks[1]].ops[2];
+
+    // `%bb0` also branches to the exit, so it can't serve as the preheader
+    assert_eq!(code.licm(func), 1);
+    let new_blocks = code.mir_code.functions[func].blocks.clone();
+    assert_eq!(new_blocks.len(), 4);
+    let preheader = new_blocks[1];
+    assert_eq!(new_blocks, vec![blocks[0], preheader, blocks[1], blocks[2]]);
+    assert_eq!(code.blocks[preheader].ops.len(), 2);
+    assert_eq!(code.blocks[preheader].ops[0], product);
+    assert_eq!(code.terminator(preheader), Some(&Instr::Br(blocks[1])));
+    assert!(!code.blocks[blocks[1]].ops.contains(&product));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    for &(n, lit) in &[(0, 0), (1, 6), (5, 18)] {
+        let arguments = vec![2, 3, n].into_iter().map(|lit| Const::Int { lit, ty: Type::u8() }).collect();
+        let result = interpreter.eval(func, arguments, vec![]).unwrap();
+        assert_eq!(result, Const::Int { lit, ty: Type::u8() });
+    }
+}
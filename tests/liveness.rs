@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+
+use dire::{Code, OpId};
+use dire::mir::FuncId;
+use dire::mir::cfg::Cfg;
+use dire::mir::liveness::Liveness;
+use string_interner::StringInterner;
+
+fn set(ops: &[OpId]) -> HashSet<OpId> {
+    ops.iter().copied().collect()
+}
+
+#[test]
+fn phi_inputs_are_live_out_of_their_incoming_blocks() {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter u8
+    %op2 = mir.parameter u8
+    %op3 = mir.parameter bool
+    %op4 = mir.cond_br %op3, %bb1, %bb2
+%bb1:
+    %op5 = mir.intrinsic Add u8 (%op1, %op2)
+    %op6 = mir.br %bb3
+%bb2:
+    %op7 = mir.br %bb3
+%bb3:
+    %op8 = mir.phi [%bb1: %op5, %bb2: %op1]
+    %op9 = mir.ret %op8
+}
+"#, &mut interner).unwrap();
+    let function = &code.mir_code.functions[FuncId::new(0)];
+    let cfg = Cfg::new(&code, function);
+    let liveness = Liveness::new(&code, function, &cfg);
+    let blocks = &function.blocks;
+    let op = |block: usize, index: usize| code.blocks[blocks[block]].ops[index];
+    let (a, b, c, sum, phi) = (op(0, 0), op(0, 1), op(0, 2), op(1, 0), op(3, 0));
+
+    assert!(liveness.live_in(blocks[0]).is_empty());
+    assert_eq!(liveness.live_out(blocks[0]), &set(&[a, b]));
+    assert_eq!(liveness.live_in(blocks[1]), &set(&[a, b]));
+    assert_eq!(liveness.live_out(blocks[1]), &set(&[sum]));
+    assert_eq!(liveness.live_in(blocks[2]), &set(&[a]));
+    assert_eq!(liveness.live_out(blocks[2]), &set(&[a]));
+    assert!(liveness.live_in(blocks[3]).is_empty());
+    assert!(liveness.is_live_out(sum, blocks[1]) && !liveness.is_live_out(sum, blocks[2]));
+
+    assert_eq!(
+        liveness.live_after_ops(&code, blocks[0]),
+        vec![set(&[a]), set(&[a, b]), set(&[a, b, c]), set(&[a, b])],
+    );
+    assert_eq!(liveness.live_after_ops(&code, blocks[3]), vec![set(&[phi]), set(&[])]);
+    assert_eq!(liveness.max_live(), 3);
+}
@@ -0,0 +1,54 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId, Instr, PhiEdge};
+use dire::mir::interp::Interpreter;
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+#[test]
+fn stores_on_both_arms_become_a_phi_at_the_join() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.alloca u8
+    %op3 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op4 = mir.const int u8 1
+    %op5 = mir.store %op4 to %op2
+    %op6 = mir.br %bb3
+%bb2:
+    %op7 = mir.const int u8 2
+    %op8 = mir.store %op7 to %op2
+    %op9 = mir.br %bb3
+%bb3:
+    %op10 = mir.load %op2
+    %op11 = mir.ret %op10
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    assert_eq!(code.mem2reg(func), 1);
+
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let memory_access = blocks.iter()
+        .flat_map(|&block| &code.blocks[block].ops)
+        .find(|&&op| matches!(code.ops[op].as_mir_instr(), Some(Instr::Alloca(_) | Instr::Load(_) | Instr::Store { .. })));
+    assert_eq!(memory_access, None);
+    let join = &code.blocks[blocks[3]].ops;
+    let (one, two) = (code.blocks[blocks[1]].ops[0], code.blocks[blocks[2]].ops[0]);
+    let mut incoming = match code.ops[join[0]].as_mir_instr() {
+        Some(Instr::Phi { incoming }) => incoming.to_vec(),
+        instr => panic!("expected a phi, found {:?}", instr),
+    };
+    incoming.sort_by_key(|edge| edge.bb);
+    assert_eq!(incoming, vec![PhiEdge { bb: blocks[1], value: one }, PhiEdge { bb: blocks[2], value: two }]);
+    assert_eq!(code.ops[join[1]].as_mir_instr(), Some(&Instr::Ret(join[0])));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    for &(condition, lit) in &[(true, 1), (false, 2)] {
+        let result = interpreter.eval(func, vec![Const::Bool(condition)], vec![]).unwrap();
+        assert_eq!(result, Const::Int { lit, ty: Type::u8() });
+    }
+}
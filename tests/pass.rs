@@ -0,0 +1,74 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{FuncId, Instr};
+use dire::mir::pass::{DeadCodeElimination, Pass, PassManager, PipelineError, SimplifyCfg};
+use string_interner::StringInterner;
+
+const SRC: &str = r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const int u8 1
+    %op2 = mir.intrinsic Add u8 (%op1, %op1)
+    %op3 = mir.ret %op1
+}
+"#;
+
+/// Appends an instr after the terminator of the entry block of each function
+struct Break;
+
+impl Pass for Break {
+    fn name(&self) -> &str { "break" }
+
+    fn run_on_function(&mut self, code: &mut Code, func: FuncId) -> usize {
+        let block = code.mir_code.functions[func].blocks[0];
+        let op = code.push_mir_instr(Instr::Void);
+        code.blocks[block].ops.push(op);
+        1
+    }
+}
+
+#[test]
+fn pipelines_are_looked_up_by_name() {
+    assert_eq!(PassManager::with_pipeline("default", Arch::X86_64).unwrap().passes().len(), 8);
+    assert_eq!(PassManager::with_pipeline("fast", Arch::X86_64).err(), Some(PipelineError::UnknownPipeline("fast".to_string())));
+}
+
+#[test]
+fn verify_each_stops_at_the_first_invalid_output() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let mut manager = PassManager::new();
+    manager.add_pass(Box::new(Break));
+    manager.add_pass(Box::new(DeadCodeElimination));
+    manager.run(&mut code, &interner).unwrap();
+    assert_eq!(manager.stats().len(), 2);
+
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let mut manager = PassManager::new();
+    manager.set_verify_each(true);
+    manager.add_pass(Box::new(Break));
+    manager.add_pass(Box::new(DeadCodeElimination));
+    let error = manager.run(&mut code, &interner).unwrap_err();
+    assert_eq!(error.pass, "break");
+    assert_eq!(error.errors.len(), 1);
+    assert_eq!(error.errors[0].0, FuncId::new(0));
+    assert_eq!(manager.stats().len(), 1);
+}
+
+#[test]
+fn dump_around_records_mir_before_and_after() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let mut manager = PassManager::new();
+    manager.set_dump_around(Some("dce".to_string()));
+    manager.add_pass(Box::new(SimplifyCfg));
+    manager.add_pass(Box::new(DeadCodeElimination));
+    manager.run(&mut code, &interner).unwrap();
+
+    let dumps = manager.dumps();
+    assert_eq!(dumps.len(), 1);
+    assert_eq!(dumps[0].pass, "dce");
+    assert!(dumps[0].before.contains("mir.intrinsic Add"), "{}", dumps[0].before);
+    assert!(!dumps[0].after.contains("mir.intrinsic Add"), "{}", dumps[0].after);
+    assert_eq!(dumps[0].after, code.display_mir(&interner).to_string());
+}
@@ -0,0 +1,57 @@
+use dire::Code;
+use dire::mir::{FuncId, Instr};
+use dire::mir::rewrite::RewriteError;
+use string_interner::StringInterner;
+
+const SRC: &str = r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const int u8 1
+    %op2 = mir.const int u8 2
+    %op3 = mir.intrinsic Add u8 (%op1, %op1)
+    %op4 = mir.ret %op3
+}
+"#;
+
+#[test]
+fn replace_uses_and_erase() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let block = code.mir_code.functions[func].blocks[0];
+    let ops = code.blocks[block].ops.clone();
+    let (one, two, sum) = (ops[0], ops[1], ops[2]);
+
+    assert!(matches!(code.erase_op(block, one), Err(RewriteError::HasUses(op)) if op == one));
+    assert_eq!(code.replace_all_uses(one, two), 2);
+    assert_eq!(code.uses(two), &[sum, sum]);
+    code.erase_op(block, one).unwrap();
+    assert_eq!(code.blocks[block].ops, &ops[1..]);
+
+    let remap = code.compact_ops();
+    assert_eq!(remap[one], None);
+    let (two, sum) = (remap[two].unwrap(), remap[sum].unwrap());
+    assert_eq!(code.ops[sum].as_mir_instr().unwrap().operands().as_slice(), &[two, two]);
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
+
+#[test]
+fn split_then_merge() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(SRC, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let block = code.mir_code.functions[func].blocks[0];
+    let ops = code.blocks[block].ops.clone();
+
+    let tail = code.split_block(func, block, 2);
+    assert_eq!(code.mir_code.functions[func].blocks, vec![block, tail]);
+    assert_eq!(code.blocks[tail].ops, &ops[2..]);
+    assert!(matches!(code.ops[*code.blocks[block].ops.last().unwrap()].as_mir_instr(), Some(&Instr::Br(target)) if target == tail));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+
+    assert!(matches!(code.merge_blocks(func, tail, block), Err(RewriteError::EntryBlock)));
+    code.merge_blocks(func, block, tail).unwrap();
+    assert_eq!(code.mir_code.functions[func].blocks, vec![block]);
+    assert_eq!(code.blocks[block].ops, ops);
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
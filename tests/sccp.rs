@@ -0,0 +1,71 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId, Instr};
+use dire::ty::Type;
+use smallvec::smallvec;
+use string_interner::StringInterner;
+
+#[test]
+fn branches_on_constants_are_pruned() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const int u8 1
+    %op2 = mir.intrinsic Add u8 (%op1, %op1)
+    %op3 = mir.const int u8 2
+    %op4 = mir.intrinsic Eq bool (%op2, %op3)
+    %op5 = mir.cond_br %op4, %bb1, %bb2
+%bb1:
+    %op6 = mir.br %bb3
+%bb2:
+    %op7 = mir.br %bb3
+%bb3:
+    %op8 = mir.phi [%bb1: %op1, %bb2: %op3]
+    %op9 = mir.ret %op8
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    code.sccp(func, Arch::X86_64);
+
+    assert_eq!(code.mir_code.functions[func].blocks, vec![blocks[0], blocks[1], blocks[3]]);
+    assert_eq!(code.terminator(blocks[0]), Some(&Instr::Br(blocks[1])));
+    let result = match code.terminator(blocks[3]) {
+        Some(&Instr::Ret(value)) => code.ops[value].as_mir_instr(),
+        terminator => panic!("expected a ret, found {:?}", terminator),
+    };
+    assert_eq!(result, Some(&Instr::Const(Const::Int { lit: 1, ty: Type::u8() })));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
+
+#[test]
+fn only_well_typed_calls_through_constants_are_devirtualized() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const func fn(u8) -> u8 @f1
+    %op2 = mir.const func fn(u16) -> u8 @f1
+    %op3 = mir.const int u8 4
+    %op4 = mir.call_indirect %op1(%op3)
+    %op5 = mir.call_indirect %op2(%op3)
+    %op6 = mir.ret %op4
+}
+
+fn @f1 "double" -> u8 {
+%bb1:
+    %op7 = mir.parameter u8
+    %op8 = mir.intrinsic Add u8 (%op7, %op7)
+    %op9 = mir.ret %op8
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let entry = code.mir_code.functions[func].blocks[0];
+    let (four, well_typed, mistyped) = (code.blocks[entry].ops[2], code.blocks[entry].ops[3], code.blocks[entry].ops[4]);
+    code.sccp(func, Arch::X86_64);
+
+    let call = Instr::Call { arguments: smallvec![four], generic_arguments: Vec::new(), func: FuncId::new(1) };
+    assert_eq!(code.ops[well_typed].as_mir_instr(), Some(&call));
+    assert!(matches!(code.ops[mistyped].as_mir_instr(), Some(Instr::CallIndirect { .. })));
+}
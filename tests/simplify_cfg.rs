@@ -0,0 +1,82 @@
+use dire::Code;
+use dire::arch::Arch;
+use dire::mir::{Const, FuncId, Instr, PhiEdge};
+use dire::mir::interp::Interpreter;
+use dire::ty::Type;
+use string_interner::StringInterner;
+
+#[test]
+fn jumps_through_empty_blocks_are_threaded() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.const int u8 1
+    %op3 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op4 = mir.br %bb3
+%bb2:
+    %op5 = mir.const int u8 2
+    %op6 = mir.br %bb3
+%bb3:
+    %op7 = mir.phi [%bb1: %op2, %bb2: %op5]
+    %op8 = mir.ret %op7
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let one = code.blocks[blocks[0]].ops[1];
+    code.simplify_cfg(func);
+
+    let condition = code.blocks[blocks[0]].ops[0];
+    assert_eq!(
+        code.terminator(blocks[0]),
+        Some(&Instr::CondBr { condition, true_bb: blocks[3], false_bb: blocks[2] }),
+    );
+    let phi = code.blocks[blocks[3]].ops[0];
+    assert!(matches!(code.ops[phi].as_mir_instr(), Some(Instr::Phi { incoming }) if incoming.contains(&PhiEdge { bb: blocks[0], value: one })));
+
+    code.eliminate_dead_code(func);
+    assert_eq!(code.mir_code.functions[func].blocks, vec![blocks[0], blocks[2], blocks[3]]);
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+    let mut interpreter = Interpreter::new(&code, Arch::X86_64);
+    for &(condition, lit) in &[(true, 1), (false, 2)] {
+        let result = interpreter.eval(func, vec![Const::Bool(condition)], vec![]).unwrap();
+        assert_eq!(result, Const::Int { lit, ty: Type::u8() });
+    }
+}
+
+#[test]
+fn constant_branches_fold_and_blocks_merge() {
+    let mut interner = StringInterner::default();
+    let mut code = Code::parse_mir(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.const bool true
+    %op2 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op3 = mir.const int u8 1
+    %op4 = mir.br %bb3
+%bb2:
+    %op5 = mir.const int u8 2
+    %op6 = mir.br %bb3
+%bb3:
+    %op7 = mir.phi [%bb1: %op3, %bb2: %op5]
+    %op8 = mir.ret %op7
+}
+"#, &mut interner).unwrap();
+    let func = FuncId::new(0);
+    let blocks = code.mir_code.functions[func].blocks.clone();
+    let one = code.blocks[blocks[1]].ops[0];
+
+    // The unreachable `%bb2` still branches to `%bb3`, which keeps it from being merged
+    code.simplify_cfg(func);
+    assert_eq!(code.terminator(blocks[0]), Some(&Instr::Br(blocks[3])));
+    code.eliminate_dead_code(func);
+    code.simplify_cfg(func);
+
+    assert_eq!(code.mir_code.functions[func].blocks, vec![blocks[0]]);
+    assert_eq!(code.terminator(blocks[0]), Some(&Instr::Ret(one)));
+    assert!(code.verify_function(&code.mir_code.functions[func]).is_empty());
+}
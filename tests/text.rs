@@ -0,0 +1,53 @@
+use dire::Code;
+use dire::mir::FuncId;
+use string_interner::StringInterner;
+
+#[test]
+fn round_trip_multiple_functions() {
+    let mut interner = StringInterner::default();
+    let src = r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op0 = mir.void
+    %op1 "x" = mir.const int u8 1
+    %op2 "x.1" = mir.const int u8 2
+    %op3 = mir.ret %op0
+}
+fn @f1 "g" -> void {
+%bb1:
+    %op0 = mir.void
+    %op4 = mir.ret %op0
+}
+"#;
+    let mut code = Code::parse_mir(src, &mut interner).unwrap();
+    let printed = code.display_mir(&interner).to_string();
+    let reparsed = Code::parse_mir(&printed, &mut interner).unwrap();
+    assert_eq!(reparsed.display_mir(&interner).to_string(), printed);
+
+    let namespace = &mut code.mir_code.functions[FuncId::new(0)].instr_namespace;
+    assert_eq!(namespace.insert("x"), "x.2");
+    assert_eq!(namespace.insert("x"), "x.3");
+    assert_eq!(namespace.insert("y"), "y");
+}
+
+#[test]
+fn intrinsic_names_round_trip() {
+    let mut interner = StringInterner::default();
+    let src = r#"
+fn @f0 "f" -> bool {
+%bb0:
+    %op1 = mir.parameter u8
+    %op2 = mir.intrinsic LessOrEq bool (%op1, %op1)
+    %op3 = mir.intrinsic PrintType void (%op1)
+    %op4 = mir.ret %op2
+}
+"#;
+    let code = Code::parse_mir(src, &mut interner).unwrap();
+    let printed = code.display_mir(&interner).to_string();
+    assert!(printed.contains("mir.intrinsic LessOrEq bool (%op1, %op1)"));
+    assert!(printed.contains("mir.intrinsic PrintType void (%op1)"));
+    let reparsed = Code::parse_mir(&printed, &mut interner).unwrap();
+    assert_eq!(reparsed.display_mir(&interner).to_string(), printed);
+
+    assert!(Code::parse_mir(&src.replace("LessOrEq", "LessOrEqual"), &mut interner).is_err());
+}
@@ -0,0 +1,28 @@
+use dire::{Code, Op};
+use dire::mir::{Const, Instr};
+use dire::ty::Type;
+
+#[test]
+fn use_lists_follow_mutations() {
+    let mut code = Code::default();
+    let a = code.push_mir_instr(Instr::Const(Const::Int { lit: 1, ty: Type::u8() }));
+    let b = code.push_mir_instr(Instr::Const(Const::Int { lit: 2, ty: Type::u8() }));
+    let user = code.push_mir_instr(Instr::Ret(a));
+    assert_eq!(code.uses(a), &[user]);
+    assert!(!code.has_uses(b));
+
+    code.set_mir_instr(user, Instr::Ret(b));
+    assert!(!code.has_uses(a));
+    assert_eq!(code.uses(b), &[user]);
+
+    assert_eq!(code.replace_all_uses(b, a), 1);
+    assert_eq!(code.uses(a), &[user]);
+    assert!(!code.has_uses(b));
+
+    // Writing to `ops` directly leaves the use lists stale until they're rebuilt
+    code.ops[user] = Op::MirInstr(Instr::Ret(b));
+    assert_eq!(code.uses(a), &[user]);
+    code.rebuild_uses();
+    assert!(!code.has_uses(a));
+    assert_eq!(code.uses(b), &[user]);
+}
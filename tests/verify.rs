@@ -0,0 +1,87 @@
+use dire::Code;
+use dire::mir::FuncId;
+use dire::mir::verify::VerifyErrorKind;
+use string_interner::StringInterner;
+
+fn verify(src: &str) -> Vec<VerifyErrorKind> {
+    let mut interner = StringInterner::default();
+    let code = Code::parse_mir(src, &mut interner).unwrap();
+    code.verify_function(&code.mir_code.functions[FuncId::new(0)]).into_iter()
+        .map(|error| error.kind)
+        .collect()
+}
+
+#[test]
+fn valid_function_has_no_errors() {
+    let errors = verify(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter u8
+    %op2 = mir.br %bb1
+%bb1:
+    %op3 = mir.ret %op1
+}
+"#);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn misplaced_terminators_and_parameters() {
+    let errors = verify(r#"
+fn @f0 "f" -> void {
+%bb0:
+    %op1 = mir.const int u8 1
+    %op2 = mir.parameter u8
+    %op3 = mir.br %bb1
+    %op4 = mir.void
+%bb1:
+    %op5 = mir.ret %op0
+}
+"#);
+    assert!(matches!(errors[..], [
+        VerifyErrorKind::MisplacedParameter,
+        VerifyErrorKind::TerminatorNotAtEnd,
+        VerifyErrorKind::MissingTerminator,
+    ]), "{:?}", errors);
+}
+
+#[test]
+fn use_not_dominated_by_its_definition() {
+    let errors = verify(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op3 = mir.const int u8 1
+    %op4 = mir.br %bb2
+%bb2:
+    %op5 = mir.ret %op3
+}
+"#);
+    assert!(matches!(errors[..], [VerifyErrorKind::UseNotDominated(_)]), "{:?}", errors);
+}
+
+#[test]
+fn phis_must_lead_their_block_and_match_its_predecessors() {
+    let errors = verify(r#"
+fn @f0 "f" -> u8 {
+%bb0:
+    %op1 = mir.parameter bool
+    %op2 = mir.const int u8 1
+    %op3 = mir.cond_br %op1, %bb1, %bb2
+%bb1:
+    %op4 = mir.br %bb2
+%bb2:
+    %op5 = mir.phi [%bb1: %op2]
+    %op6 = mir.const int u8 2
+    %op7 = mir.phi [%bb0: %op2, %bb1: %op6]
+    %op8 = mir.ret %op5
+}
+"#);
+    assert!(matches!(errors[..], [
+        VerifyErrorKind::PhiIncomingMismatch,
+        VerifyErrorKind::MisplacedPhi,
+        VerifyErrorKind::UseNotDominated(_),
+    ]), "{:?}", errors);
+}